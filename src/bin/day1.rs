@@ -1,12 +1,14 @@
 #![warn(clippy::pedantic)]
+#![allow(dead_code)]
 
-use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::num::ParseIntError;
 use std::path::PathBuf;
 
+use aoc2024::FrequencyTable;
+
 /// Tries to convert a space-separated &str representing columns of integers to a Vec<u32>.
 fn to_vec_int(s: &str) -> Result<Vec<u32>, ParseIntError> {
     s.split_whitespace()
@@ -25,20 +27,45 @@ fn distance_between(first: &[u32], second: &[u32]) -> usize {
 
 /// Computes the similarity score between two slices.
 fn similarity_between(first: &[u32], second: &[u32]) -> usize {
-    let freqs = count_occurrences(second);
-    first
+    similarity_breakdown(first, second).0
+}
+
+/// Computes the similarity score between two slices, along with the contribution of each value in
+/// `first`, in order.
+fn similarity_breakdown(first: &[u32], second: &[u32]) -> (usize, Vec<(u32, usize)>) {
+    let freqs = FrequencyTable::from(second);
+    let contributions: Vec<(u32, usize)> = first
         .iter()
-        .map(|n| *n as usize * freqs.get(n).unwrap_or(&0))
-        .sum()
+        .map(|&n| (n, n as usize * freqs.frequency(&n)))
+        .collect();
+
+    (contributions.iter().map(|(_, c)| c).sum(), contributions)
 }
 
-/// Counts the occurrences of values in `nums`.
-fn count_occurrences(nums: &[u32]) -> HashMap<u32, usize> {
-    let mut m = HashMap::new();
-    for n in nums {
-        *m.entry(*n).or_default() += 1;
-    }
-    m
+/// Returns the value in `first` contributing the most to the similarity score, along with its
+/// contribution.
+///
+/// If several values contribute the same amount, the first one is returned.
+fn max_contributor(first: &[u32], second: &[u32]) -> Option<(u32, usize)> {
+    let (_, contributions) = similarity_breakdown(first, second);
+    contributions.into_iter().rev().max_by_key(|&(_, c)| c)
+}
+
+/// Computes a similarity score between two slices, using `score` to weigh each value.
+///
+/// `score` receives each value in `first` along with its frequency in `second`, and returns the
+/// value's contribution to the total.
+fn similarity_with<F>(first: &[u32], second: &[u32], score: F) -> usize
+where
+    F: Fn(u32, usize) -> usize,
+{
+    let freqs = FrequencyTable::from(second);
+    first.iter().map(|n| score(*n, freqs.frequency(n))).sum()
+}
+
+/// Counts the values in `first` that do not appear in `second`.
+fn inverse_similarity(first: &[u32], second: &[u32]) -> usize {
+    similarity_with(first, second, |_, f| usize::from(f == 0))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -69,3 +96,47 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST_COL: &[u32] = &[3, 4, 2, 1, 3, 3];
+    const SECOND_COL: &[u32] = &[4, 3, 5, 3, 9, 3];
+
+    #[test]
+    fn similarity_is_computed_successfully() {
+        assert_eq!(similarity_between(FIRST_COL, SECOND_COL), 31);
+    }
+
+    #[test]
+    fn similarity_is_broken_down_by_value() {
+        let (total, contributions) = similarity_breakdown(FIRST_COL, SECOND_COL);
+        assert_eq!(total, 31);
+        assert_eq!(contributions.len(), FIRST_COL.len());
+        assert_eq!(contributions.iter().map(|(_, c)| c).sum::<usize>(), total);
+
+        for (&n, &(v, c)) in FIRST_COL.iter().zip(&contributions) {
+            let freq = SECOND_COL.iter().filter(|&&m| m == n).count();
+            assert_eq!(v, n);
+            assert_eq!(c, n as usize * freq);
+        }
+        assert_eq!(contributions[2], (2, 0));
+        assert_eq!(contributions[3], (1, 0));
+
+        assert_eq!(max_contributor(FIRST_COL, SECOND_COL), Some((3, 9)));
+        assert_eq!(max_contributor(&[], SECOND_COL), None);
+    }
+
+    #[test]
+    fn inverse_similarity_counts_missing_values() {
+        assert_eq!(inverse_similarity(FIRST_COL, SECOND_COL), 2);
+    }
+
+    #[test]
+    fn similarity_accepts_custom_scores() {
+        let present = similarity_with(FIRST_COL, SECOND_COL, |_, f| usize::from(f > 0) * 10);
+
+        assert_eq!(present, 40);
+    }
+}
@@ -1,13 +1,54 @@
 #![warn(clippy::pedantic)]
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::num::ParseIntError;
 use std::path::PathBuf;
 
 type Level = u32;
 
+/// Possible errors when reading reports.
+#[derive(Debug)]
+enum ReportError {
+    /// The reports could not be read.
+    Io(io::Error),
+    /// A level is malformed.
+    MalformedLevel(ParseIntError),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read reports: {e}"),
+            Self::MalformedLevel(e) => write!(f, "malformed level: {e}"),
+        }
+    }
+}
+
+impl Error for ReportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::MalformedLevel(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ReportError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ParseIntError> for ReportError {
+    fn from(e: ParseIntError) -> Self {
+        Self::MalformedLevel(e)
+    }
+}
+
 #[allow(dead_code)]
 /// A report containing either safe or unsafe measurement levels.
 #[derive(Debug)]
@@ -25,10 +66,68 @@ impl TryFrom<&str> for Report {
             .map(str::parse)
             .collect::<Result<Vec<_>, _>>()?;
 
+        Ok(Self::from(v))
+    }
+}
+
+impl Report {
+    /// Lazily parses the reports in `reader`, one per line.
+    ///
+    /// Each line yields either a report or the error found while reading or parsing it, so that
+    /// callers can decide whether to stop at the first error.
+    fn from_reader<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Report, ReportError>> {
+        reader.lines().map(|line| Ok(Report::try_from(&*line?)?))
+    }
+}
+
+impl From<Vec<Level>> for Report {
+    fn from(v: Vec<Level>) -> Self {
         if are_levels_safe(&v) {
-            return Ok(Self::Safe(SafeReport(v)));
+            return Self::Safe(SafeReport(v));
+        }
+        Self::Unsafe(UnsafeReport(v))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Report::Safe(r) => r.fmt(f),
+            Report::Unsafe(r) => r.fmt(f),
+        }
+    }
+}
+
+#[allow(dead_code)]
+/// Shared access to the levels in a report.
+trait ReportLevels {
+    /// Returns the levels in `self`.
+    fn levels(&self) -> &[Level];
+
+    /// Returns an iterator over the levels in `self`.
+    fn levels_iter(&self) -> impl Iterator<Item = Level> + '_ {
+        self.levels().iter().copied()
+    }
+}
+
+impl ReportLevels for Report {
+    fn levels(&self) -> &[Level] {
+        match self {
+            Report::Safe(r) => r.levels(),
+            Report::Unsafe(r) => r.levels(),
         }
-        Ok(Self::Unsafe(UnsafeReport(v)))
+    }
+}
+
+impl ReportLevels for SafeReport {
+    fn levels(&self) -> &[Level] {
+        &self.0
+    }
+}
+
+impl ReportLevels for UnsafeReport {
+    fn levels(&self) -> &[Level] {
+        &self.0
     }
 }
 
@@ -37,13 +136,50 @@ impl TryFrom<&str> for Report {
 #[derive(Debug)]
 struct SafeReport(Vec<Level>);
 
+impl fmt::Display for SafeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for l in &self.0 {
+            write!(f, "{l} ")?;
+        }
+        write!(f, "[safe]")
+    }
+}
+
 /// A report containing unsafe levels.
 #[derive(Debug)]
 struct UnsafeReport(Vec<Level>);
 
+impl fmt::Display for UnsafeReport {
+    /// Formats the levels in `self`, marking the first one that breaks safety rules with `*`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let violation = match classify_levels(&self.0) {
+            SafetyResult::Safe => None,
+            SafetyResult::Unsafe { index } => Some(index),
+        };
+        for (i, l) in self.0.iter().enumerate() {
+            let mark = if Some(i) == violation { "*" } else { "" };
+            write!(f, "{l}{mark} ")?;
+        }
+        write!(f, "[unsafe]")
+    }
+}
+
+/// The outcome of checking a sequence of levels against safety rules.
+#[derive(Debug, PartialEq)]
+enum SafetyResult {
+    /// Every level follows safety rules.
+    Safe,
+    /// The level at `index` is the first one breaking safety rules.
+    Unsafe { index: usize },
+}
+
 impl UnsafeReport {
     /// Tries to build a `SafeReport` by removing up to one level.
     fn try_dampen(&self) -> Result<SafeReport, &'static str> {
+        if are_levels_safe(&self.0) {
+            return Ok(SafeReport(self.0.clone()));
+        }
+
         for (i, _) in self.0.iter().enumerate() {
             let v = self.exclude_level(i);
             if are_levels_safe(&v) {
@@ -51,10 +187,95 @@ impl UnsafeReport {
             }
         }
 
+        Err("cannot correct report error")
+    }
+
+    /// Tries to build a `SafeReport` by removing up to one level, only trying the levels around
+    /// the first violation of safety rules.
+    ///
+    /// If the violation is found at level `k`, removing level `k` or `k - 1` usually fixes it.
+    /// This is not enough when the first two levels set the wrong direction, as in `1 3 2 1`,
+    /// where only removing the first level helps: in that case, every level is tried in turn
+    /// like `try_dampen` does.
+    fn try_dampen_fast(&self) -> Result<SafeReport, &'static str> {
+        let SafetyResult::Unsafe { index } = classify_levels(&self.0) else {
+            return Ok(SafeReport(self.0.clone()));
+        };
+
+        for i in [index, index - 1] {
+            let v = self.exclude_level(i);
+            if are_levels_safe(&v) {
+                return Ok(SafeReport(v));
+            }
+        }
+
+        self.try_dampen()
+    }
+
+    #[allow(dead_code)]
+    /// Tries to build a `SafeReport` by removing up to `n` levels.
+    ///
+    /// The safety of every sequence of levels checked is stored in `cache`, so that it is not
+    /// checked again when reached by removing the same levels in a different order.
+    fn try_dampen_n(
+        &self,
+        n: usize,
+        cache: &mut HashMap<Vec<Level>, bool>,
+    ) -> Result<SafeReport, &'static str> {
+        self.try_dampen_n_with(n, cache, &are_levels_safe)
+    }
+
+    #[allow(dead_code)]
+    /// Tries to build a `SafeReport` by removing up to `n` levels, checking safety according to
+    /// `check` and storing the answers in `cache`.
+    fn try_dampen_n_with<F>(
+        &self,
+        n: usize,
+        cache: &mut HashMap<Vec<Level>, bool>,
+        check: &F,
+    ) -> Result<SafeReport, &'static str>
+    where
+        F: Fn(&[Level]) -> bool,
+    {
+        if are_levels_safe_cached_with(&self.0, cache, check) {
+            return Ok(SafeReport(self.0.clone()));
+        }
+
+        if n > 0 {
+            for (i, _) in self.0.iter().enumerate() {
+                let r = UnsafeReport(self.exclude_level(i));
+                if let Ok(r) = r.try_dampen_n_with(n - 1, cache, check) {
+                    return Ok(r);
+                }
+            }
+        }
+
         // The Problem Dampener failed, nothing can be done.
         Err("cannot correct report error")
     }
 
+    #[allow(dead_code)]
+    /// Tries to build a `SafeReport` by removing up to one level, checking only every `stride`-th
+    /// level for safety.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is 0.
+    fn try_dampen_strided(&self, stride: usize) -> Result<SafeReport, &'static str> {
+        if are_levels_safe_strided(&self.0, stride) {
+            return Ok(SafeReport(self.0.clone()));
+        }
+
+        for (i, _) in self.0.iter().enumerate() {
+            let v = self.exclude_level(i);
+            if are_levels_safe_strided(&v, stride) {
+                return Ok(SafeReport(v));
+            }
+        }
+
+        Err("cannot correct report error")
+    }
+
     /// Returns the levels in `self`, excluding the one at `pos`.
     fn exclude_level(&self, pos: usize) -> Vec<Level> {
         self.0
@@ -66,8 +287,60 @@ impl UnsafeReport {
     }
 }
 
+#[allow(dead_code)]
+/// Checks if a slice of levels matches safety rules, looking up and storing the answer in `cache`.
+fn are_levels_safe_cached(v: &[Level], cache: &mut HashMap<Vec<Level>, bool>) -> bool {
+    are_levels_safe_cached_with(v, cache, are_levels_safe)
+}
+
+#[allow(dead_code)]
+/// Checks if a slice of levels matches safety rules according to `check`, looking up and storing
+/// the answer in `cache`.
+fn are_levels_safe_cached_with<F>(
+    v: &[Level],
+    cache: &mut HashMap<Vec<Level>, bool>,
+    check: F,
+) -> bool
+where
+    F: Fn(&[Level]) -> bool,
+{
+    if let Some(&safe) = cache.get(v) {
+        return safe;
+    }
+
+    let safe = check(v);
+    cache.insert(v.to_vec(), safe);
+    safe
+}
+
+/// Checks a slice of levels against safety rules, locating the first level breaking them.
+///
+/// A level breaks the rules if it differs from the previous one by less than 1 or more than 3, or
+/// if it changes direction with respect to the first two levels.
+fn classify_levels(v: &[Level]) -> SafetyResult {
+    let increasing = v.len() > 1 && v[0] < v[1];
+    for (i, w) in v.windows(2).enumerate() {
+        if !(1..=3).contains(&w[0].abs_diff(w[1])) || (w[0] < w[1]) != increasing {
+            return SafetyResult::Unsafe { index: i + 1 };
+        }
+    }
+    SafetyResult::Safe
+}
+
 /// Checks if a slice of levels matches safety rules.
 fn are_levels_safe(v: &[Level]) -> bool {
+    are_levels_safe_strided(v, 1)
+}
+
+/// Checks if every `stride`-th level in a slice, starting from the first, matches safety rules.
+///
+/// # Panics
+///
+/// Panics if `stride` is 0.
+fn are_levels_safe_strided(v: &[Level], stride: usize) -> bool {
+    assert!(stride > 0, "stride should be positive");
+    let v: Vec<Level> = v.iter().copied().step_by(stride).collect();
+
     if !v.is_sorted() && !v.is_sorted_by(|a, b| a >= b) {
         return false;
     }
@@ -81,33 +354,30 @@ fn are_levels_safe(v: &[Level]) -> bool {
     true
 }
 
+/// Counts the safe reports in `reader` and the unsafe ones that can be dampened, in one pass.
+///
+/// # Errors
+///
+/// Returns the first error found while reading or parsing reports.
+fn count_safe_streaming<R: BufRead>(reader: R) -> Result<(usize, usize), ReportError> {
+    let (mut safe, mut dampened) = (0, 0);
+    for report in Report::from_reader(reader) {
+        match report? {
+            Report::Safe(_) => safe += 1,
+            Report::Unsafe(r) => dampened += usize::from(r.try_dampen_fast().is_ok()),
+        }
+    }
+
+    Ok((safe, dampened))
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
     let reader = BufReader::new(File::open(dataset)?);
 
-    let mut reports = vec![];
-    for line in reader.lines() {
-        let rep = Report::try_from(&*line?)?;
-        reports.push(rep);
-    }
-
-    let safe = reports
-        .iter()
-        .filter(|&r| match r {
-            Report::Safe(_) => true,
-            Report::Unsafe(_) => false,
-        })
-        .count();
-    let dampened = reports
-        .iter()
-        .filter_map(|r| match r {
-            Report::Unsafe(r) => Some(r.try_dampen()),
-            Report::Safe(_) => None,
-        })
-        .flatten()
-        .count();
-
-    println!("Safe reports: {safe}",);
+    let (safe, dampened) = count_safe_streaming(reader)?;
+
+    println!("Safe reports: {safe}");
     println!("Dampened reports: {dampened}");
     println!("Total safe reports: {}", safe + dampened);
 
@@ -117,6 +387,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::Cell;
+    use std::io::Cursor;
 
     const GOOD_LEVELS: &[&[Level]] = &[&[7, 6, 4, 2, 1], &[1, 3, 6, 7, 9]];
     const BAD_LEVELS: &[&[Level]] = &[&[1, 2, 7, 8, 9], &[9, 7, 6, 2, 1]];
@@ -142,6 +414,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reports_are_counted_while_streaming() {
+        let lines: Vec<String> = GOOD_LEVELS
+            .iter()
+            .chain(BAD_LEVELS)
+            .chain(CORRECTIBLE_LEVELS)
+            .map(|ls| stringify_levels(ls))
+            .collect();
+        let data = lines.join("\n");
+
+        let reports: Vec<Report> = lines
+            .iter()
+            .map(|l| Report::try_from(l.as_str()).unwrap())
+            .collect();
+        let safe = reports
+            .iter()
+            .filter(|r| matches!(r, Report::Safe(_)))
+            .count();
+        let dampened = reports
+            .iter()
+            .filter(|r| matches!(r, Report::Unsafe(u) if u.try_dampen().is_ok()))
+            .count();
+
+        assert_eq!(
+            count_safe_streaming(Cursor::new(&data)).unwrap(),
+            (safe, dampened)
+        );
+        assert_eq!((safe, dampened), (2, 2));
+
+        let mut streamed = Report::from_reader(Cursor::new("1 2 3\n1 x 3\n3 2 1"));
+        assert!(matches!(streamed.next(), Some(Ok(Report::Safe(_)))));
+        assert!(matches!(
+            streamed.next(),
+            Some(Err(ReportError::MalformedLevel(_)))
+        ));
+        assert!(matches!(streamed.next(), Some(Ok(Report::Safe(_)))));
+        assert!(streamed.next().is_none());
+        assert!(count_safe_streaming(Cursor::new("1 2 3\n1 x 3")).is_err());
+    }
+
+    #[test]
+    fn report_levels_are_shared() {
+        for l in GOOD_LEVELS.iter().chain(BAD_LEVELS) {
+            let r = Report::from(l.to_vec());
+            assert_eq!(r.levels(), *l);
+            assert_eq!(r.levels_iter().collect::<Vec<_>>(), *l);
+            assert_eq!(are_levels_safe(r.levels()), matches!(r, Report::Safe(_)));
+        }
+
+        let l = CORRECTIBLE_LEVELS[0];
+        assert_eq!(SafeReport(l.to_vec()).levels(), l);
+        assert_eq!(UnsafeReport(l.to_vec()).levels(), l);
+    }
+
     #[test]
     fn unsafereport_dampener_succeeds_for_correctible_levels() {
         for l in CORRECTIBLE_LEVELS {
@@ -156,6 +482,154 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unsafereport_fast_dampener_matches_linear_scan() {
+        let edge_cases: &[&[Level]] = &[&[1, 3, 2, 1], &[3, 1, 2, 3], &[1, 9, 2, 9, 3]];
+        for l in GOOD_LEVELS
+            .iter()
+            .chain(BAD_LEVELS)
+            .chain(CORRECTIBLE_LEVELS)
+            .chain(edge_cases)
+        {
+            let r = UnsafeReport(l.to_vec());
+            let fast = r.try_dampen_fast();
+            assert_eq!(fast.is_ok(), r.try_dampen().is_ok(), "{r}");
+            if let Ok(safe) = fast {
+                assert!(are_levels_safe(safe.levels()));
+                assert!(safe.levels().len() + 1 >= l.len());
+            }
+        }
+
+        // Only removing the first level, which the fast path does not try, fixes these.
+        assert_eq!(
+            UnsafeReport(vec![1, 3, 2, 1])
+                .try_dampen_fast()
+                .unwrap()
+                .levels(),
+            [3, 2, 1]
+        );
+        assert!(UnsafeReport(vec![1, 9, 2, 9, 3]).try_dampen_fast().is_err());
+    }
+
+    #[test]
+    fn unsafereport_dampener_removes_multiple_levels() {
+        let r = UnsafeReport(vec![1, 9, 2, 9, 3]);
+        assert!(r.try_dampen().is_err());
+
+        let safe = r.try_dampen_n(2, &mut HashMap::new()).unwrap();
+        assert_eq!(safe.levels(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn level_safety_check_reuses_cache() {
+        let checks = Cell::new(0);
+        let counted = |v: &[Level]| {
+            checks.set(checks.get() + 1);
+            are_levels_safe(v)
+        };
+        let mut cache = HashMap::new();
+
+        // Removing either of the repeated levels yields the same sequence.
+        let l = CORRECTIBLE_LEVELS[1];
+        let r = UnsafeReport(l.to_vec());
+        let mut lookups = 0;
+        for i in 0..l.len() {
+            let v = r.exclude_level(i);
+            assert_eq!(
+                are_levels_safe_cached_with(&v, &mut cache, counted),
+                are_levels_safe(&v)
+            );
+            lookups += 1;
+        }
+        assert!(checks.get() < lookups);
+
+        // Every removal from a constant report yields the same, still unsafe, sequence: only one
+        // check per length must run, instead of 1 + 5 + 5 * 4 without the cache.
+        checks.set(0);
+        let r = UnsafeReport(vec![1, 1, 1, 1, 1]);
+        assert!(r
+            .try_dampen_n_with(2, &mut HashMap::new(), &counted)
+            .is_err());
+        assert_eq!(checks.get(), 3);
+
+        for l in GOOD_LEVELS
+            .iter()
+            .chain(BAD_LEVELS)
+            .chain(CORRECTIBLE_LEVELS)
+        {
+            assert_eq!(are_levels_safe_cached(l, &mut cache), are_levels_safe(l));
+        }
+    }
+
+    #[test]
+    fn level_safety_check_supports_strides() {
+        for l in GOOD_LEVELS
+            .iter()
+            .chain(BAD_LEVELS)
+            .chain(CORRECTIBLE_LEVELS)
+        {
+            assert_eq!(are_levels_safe_strided(l, 1), are_levels_safe(l));
+        }
+
+        // Only the levels at even positions are safe.
+        let l = [1, 50, 3, 2, 5, 90, 7];
+        assert!(!are_levels_safe(&l));
+        assert!(are_levels_safe_strided(&l, 2));
+        assert!(!are_levels_safe_strided(&[1, 50, 9, 2, 5], 2));
+
+        let r = UnsafeReport(vec![1, 9, 3, 9, 9, 5, 9, 7]);
+        assert!(r.try_dampen().is_err());
+        assert_eq!(
+            r.try_dampen_strided(2).unwrap().levels(),
+            [1, 9, 3, 9, 5, 9, 7]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "stride should be positive")]
+    fn level_safety_check_rejects_zero_stride() {
+        are_levels_safe_strided(&[1, 2, 3], 0);
+    }
+
+    #[test]
+    fn level_safety_check_locates_violation() {
+        for l in GOOD_LEVELS {
+            assert_eq!(classify_levels(l), SafetyResult::Safe);
+        }
+        let violations: Vec<_> = BAD_LEVELS
+            .iter()
+            .chain(CORRECTIBLE_LEVELS)
+            .map(|l| classify_levels(l))
+            .collect();
+        assert_eq!(
+            violations,
+            [2, 3, 2, 3].map(|index| SafetyResult::Unsafe { index })
+        );
+    }
+
+    #[test]
+    fn report_is_displayed_with_annotations() {
+        let r = Report::from(GOOD_LEVELS[0].to_vec());
+        assert_eq!(r.to_string(), "7 6 4 2 1 [safe]");
+        let r = Report::from(BAD_LEVELS[0].to_vec());
+        assert_eq!(r.to_string(), "1 2 7* 8 9 [unsafe]");
+        let r = Report::from(BAD_LEVELS[1].to_vec());
+        assert_eq!(r.to_string(), "9 7 6 2* 1 [unsafe]");
+
+        for l in GOOD_LEVELS
+            .iter()
+            .chain(BAD_LEVELS)
+            .chain(CORRECTIBLE_LEVELS)
+        {
+            let s = Report::from(l.to_vec()).to_string();
+            let levels: Vec<_> = s
+                .split_whitespace()
+                .map(|l| l.trim_end_matches('*'))
+                .collect();
+            assert_eq!(levels[..l.len()].join(" "), stringify_levels(l));
+        }
+    }
+
     #[test]
     fn level_safety_check_succeeds_for_good_levels() {
         for l in GOOD_LEVELS {
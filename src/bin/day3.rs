@@ -1,55 +1,157 @@
 #![warn(clippy::pedantic)]
+#![allow(dead_code)]
 
 use std::error::Error;
 use std::fs;
 use std::num::ParseIntError;
 use std::path::PathBuf;
-use std::sync::LazyLock;
 
-use regex::Regex;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while_m_n};
+use nom::character::complete::{anychar, char};
+use nom::combinator::{map, value};
+use nom::multi::many0;
+use nom::sequence::{delimited, separated_pair};
+use nom::{IResult, Parser};
+
+/// An instruction found in a corrupted program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction<'a> {
+    /// Multiplies two operands, still in their textual form.
+    Mul(&'a str, &'a str),
+    /// Enables the following `mul` instructions.
+    Do,
+    /// Disables the following `mul` instructions.
+    Dont,
+}
 
-/// Pattern to extract operands from `mul(m,n)` instructions.
-static MUL_OPERANDS_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)").expect("pattern creation should succeed")
-});
+/// Parses an operand of up to three digits.
+fn parse_operand(input: &str) -> IResult<&str, &str> {
+    take_while_m_n(1, 3, |c: char| c.is_ascii_digit()).parse(input)
+}
 
-/// Pattern to identify an ignored program region.
-///
-/// Regions to be ignored start from `don't()` and continue until the following `do()` or the end
-/// of the input, whichever comes first.
-static IGNORED_REGION_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"(?s)don't\(\)(.*?do\(\)|.*$)").expect("pattern creation should succeed")
-});
+/// Parses a single `mul(m,n)` instruction, returning its operands without converting them.
+fn parse_mul(input: &str) -> IResult<&str, (&str, &str)> {
+    delimited(
+        tag("mul("),
+        separated_pair(parse_operand, char(','), parse_operand),
+        char(')'),
+    )
+    .parse(input)
+}
+
+/// Parses a single instruction, or skips a single corrupted char.
+fn parse_instruction(input: &str) -> IResult<&str, Option<Instruction<'_>>> {
+    alt((
+        map(parse_mul, |(m, n)| Some(Instruction::Mul(m, n))),
+        value(Some(Instruction::Do), tag("do()")),
+        value(Some(Instruction::Dont), tag("don't()")),
+        value(None, anychar),
+    ))
+    .parse(input)
+}
+
+/// Parses all the instructions in `hay`, skipping the corrupted chars between them.
+fn parse_instructions(hay: &str) -> Vec<Instruction<'_>> {
+    let (_, instructions) = many0(parse_instruction)
+        .parse(hay)
+        .expect("skipping corrupted chars should succeed");
+    instructions.into_iter().flatten().collect()
+}
 
 /// Tries to extract the operands from all `mul(m,n)` instructions.
 fn extract_mul_operands(hay: &str) -> Result<Vec<(u32, u32)>, ParseIntError> {
     let mut operands = vec![];
-    for (_, [m, n]) in MUL_OPERANDS_RE.captures_iter(hay).map(|c| c.extract()) {
-        operands.push((m.parse()?, n.parse()?));
+    for i in parse_instructions(hay) {
+        if let Instruction::Mul(m, n) = i {
+            operands.push((m.parse()?, n.parse()?));
+        }
     }
 
     Ok(operands)
 }
 
 /// Tries to extract the operands from all enabled `mul(m,n)` instructions.
+///
+/// Instructions are disabled from `don't()` until the following `do()` or the end of the input,
+/// whichever comes first.
 fn extract_enabled_mul_operands(hay: &str) -> Result<Vec<(u32, u32)>, ParseIntError> {
-    extract_mul_operands(&IGNORED_REGION_RE.replace_all(hay, ""))
+    let mut enabled = true;
+    let mut operands = vec![];
+    for i in parse_instructions(hay) {
+        match i {
+            Instruction::Mul(m, n) if enabled => operands.push((m.parse()?, n.parse()?)),
+            Instruction::Mul(..) => {}
+            Instruction::Do => enabled = true,
+            Instruction::Dont => enabled = false,
+        }
+    }
+
+    Ok(operands)
 }
 
 /// Multiplies pairs of operands and sums the results.
 fn compute_total(ops: &[(u32, u32)]) -> u32 {
-    ops.iter().map(|(m, n)| m * n).sum()
+    ops.iter().copied().mul_sum().sum_products()
 }
 
+/// An iterator multiplying pairs of operands.
+struct MulSum<I>(I);
+
+impl<I> Iterator for MulSum<I>
+where
+    I: Iterator<Item = (u32, u32)>,
+{
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(m, n)| m * n)
+    }
+}
+
+impl<I> MulSum<I>
+where
+    I: Iterator<Item = (u32, u32)>,
+{
+    /// Multiplies all the pairs of operands and sums the results.
+    fn sum_products(self) -> u32 {
+        self.sum()
+    }
+
+    /// Returns the running total of the products, one pair of operands at a time.
+    fn running_total(self) -> impl Iterator<Item = u32> {
+        self.scan(0, |total, p| {
+            *total += p;
+            Some(*total)
+        })
+    }
+}
+
+/// Extension trait to multiply pairs of operands.
+trait MulSumExt: Iterator<Item = (u32, u32)> + Sized {
+    /// Wraps `self` in a `MulSum`.
+    fn mul_sum(self) -> MulSum<Self> {
+        MulSum(self)
+    }
+}
+
+impl<I> MulSumExt for I where I: Iterator<Item = (u32, u32)> {}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
     let data = fs::read_to_string(dataset)?;
 
-    let operands = extract_mul_operands(&data)?;
-    let enabled_operands = extract_enabled_mul_operands(&data)?;
+    let total = extract_mul_operands(&data)?
+        .into_iter()
+        .mul_sum()
+        .sum_products();
+    let enabled_total = extract_enabled_mul_operands(&data)?
+        .into_iter()
+        .mul_sum()
+        .sum_products();
 
-    println!("Total (all muls):     {}", compute_total(&operands));
-    println!("Total (enabled muls): {}", compute_total(&enabled_operands));
+    println!("Total (all muls):     {total}");
+    println!("Total (enabled muls): {enabled_total}");
 
     Ok(())
 }
@@ -78,8 +180,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parser_rejects_malformed_mul() {
+        assert_eq!(parse_mul("mul(44,46)rest"), Ok(("rest", ("44", "46"))));
+        for hay in [
+            "mul(4*",
+            "mul(6,9!",
+            "?(12,34)",
+            "mul ( 2 , 4 )",
+            "mul(1234,5)",
+            "mul(,5)",
+        ] {
+            assert!(parse_mul(hay).is_err(), "{hay} should not parse");
+            assert_eq!(extract_mul_operands(hay).unwrap(), []);
+        }
+        assert_eq!(
+            extract_mul_operands("mul(1234,5)mul(123,4)").unwrap(),
+            [(123, 4)]
+        );
+    }
+
+    #[test]
+    fn disabled_regions_extend_to_end_of_input() {
+        assert_eq!(
+            extract_enabled_mul_operands(
+                "mul(1,2)don't()mul(3,4)don't()do()mul(5,6)don't()mul(7,8)"
+            )
+            .unwrap(),
+            [(1, 2), (5, 6)]
+        );
+    }
+
     #[test]
     fn operands_total_computes_successfully() {
         assert_eq!(compute_total(&[(2, 4), (5, 5), (11, 8), (8, 5)]), 161);
     }
+
+    #[test]
+    fn operands_are_summed_by_iterator_adapter() {
+        let ops = extract_mul_operands(HAY).unwrap();
+
+        assert_eq!(ops.clone().into_iter().mul_sum().sum_products(), 161);
+        assert_eq!(
+            ops.into_iter()
+                .mul_sum()
+                .running_total()
+                .collect::<Vec<_>>(),
+            [8, 33, 121, 161]
+        );
+    }
 }
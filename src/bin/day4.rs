@@ -1,8 +1,16 @@
 #![warn(clippy::pedantic)]
+#![allow(dead_code)]
 
 use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::iter::Enumerate;
+use std::ops::{Index, Range};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::{slice, vec};
+
+use rayon::prelude::*;
 
 /// Orientation of a matrix.
 enum Orientation {
@@ -16,44 +24,622 @@ enum Direction {
     RightToLeft,
 }
 
-/// An n*n matrix containing the haystack to examine.
-struct SquareMatrix {
+/// Directions in which a slice may match another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MatchMode {
+    /// The slices match element by element.
+    Forward,
+    /// One slice matches the other when reversed.
+    Backward,
+    /// The slices match in either direction.
+    Either,
+}
+
+/// A quarter of a matrix.
+///
+/// For odd dimensions, the middle row or column belongs to no quadrant.
+#[derive(Clone, Copy, Debug)]
+enum Quadrant {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Possible errors when building a matrix.
+#[derive(Debug, PartialEq)]
+enum MatrixError {
+    /// The data cannot be split in rows of the requested width.
+    NonDivisible { len: usize, width: usize },
+    /// The data cannot be laid out in a square.
+    NonSquare { len: usize, sqrt: f64 },
+    /// There is no data to build a matrix from.
+    Empty,
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NonDivisible { len, width } => {
+                write!(f, "cannot split {len} elements in rows of width {width}")
+            }
+            Self::NonSquare { len, sqrt } => {
+                write!(
+                    f,
+                    "expected square matrix but got {len} elements (sqrt≈{sqrt:.2})"
+                )
+            }
+            Self::Empty => write!(f, "empty matrix"),
+        }
+    }
+}
+
+impl Error for MatrixError {}
+
+/// Possible errors when decoding a matrix from bytes.
+#[derive(Debug, PartialEq)]
+enum ByteDecodeError {
+    /// The data is too short to hold the width of the matrix.
+    TooShort,
+    /// The byte at the given offset is not an ASCII character.
+    NonAscii(u8, usize),
+    /// The characters cannot be split in non-empty rows of the encoded width.
+    LengthMismatch { len: usize, width: usize },
+}
+
+impl fmt::Display for ByteDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "data too short to hold a width"),
+            Self::NonAscii(b, offset) => write!(f, "non-ASCII byte {b:#04x} at offset {offset}"),
+            Self::LengthMismatch { len, width } => {
+                write!(f, "cannot split {len} characters in rows of width {width}")
+            }
+        }
+    }
+}
+
+impl Error for ByteDecodeError {}
+
+/// Possible errors when searching a matrix.
+#[derive(Debug, PartialEq)]
+enum SearchError {
+    /// The needle is too short for two of them to intersect.
+    NeedleTooShort(usize),
+    /// The needle has no midpoint.
+    EvenNeedleLength(usize),
+    /// The needle has a midpoint, so it cannot intersect between elements.
+    OddNeedleLength(usize),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NeedleTooShort(len) => write!(f, "needle too short: {len} elements"),
+            Self::EvenNeedleLength(len) => write!(f, "needle has even length: {len} elements"),
+            Self::OddNeedleLength(len) => write!(f, "needle has odd length: {len} elements"),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+/// A matrix of elements, stored row by row.
+#[derive(Clone, Debug, PartialEq)]
+struct Matrix<T> {
     /// Raw data.
-    blob: Vec<char>,
-    /// Length of a side of the matrix.
+    blob: Vec<T>,
+    /// Length of a single row of the matrix.
+    width: usize,
+}
+
+/// A matrix containing the haystack to examine.
+type CharMatrix = Matrix<char>;
+
+/// The shape most puzzle inputs come in.
+type SquareMatrix = CharMatrix;
+
+impl<T: Clone + PartialEq> Matrix<T> {
+    /// Creates a new `Matrix` from the data in `blob`, split in rows of `width` elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `blob` is empty or its `.len()` is not a multiple of `width`.
+    fn new_with_width(blob: &[T], width: usize) -> Result<Self, MatrixError> {
+        if blob.is_empty() {
+            return Err(MatrixError::Empty);
+        }
+        if width == 0 || !blob.len().is_multiple_of(width) {
+            return Err(MatrixError::NonDivisible {
+                len: blob.len(),
+                width,
+            });
+        }
+
+        let blob = blob.to_vec();
+        Ok(Self { blob, width })
+    }
+
+    /// Returns the amount of rows in `self`.
+    fn height(&self) -> usize {
+        self.blob.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// Returns a new matrix with the same shape as `self`, applying `f` to every element.
+    fn map<U, F>(&self, f: F) -> Matrix<U>
+    where
+        F: Fn(T) -> U,
+    {
+        Matrix {
+            blob: self.blob.iter().cloned().map(f).collect(),
+            width: self.width,
+        }
+    }
+
+    /// Returns the `(row, col)` coordinates of every element matching `predicate`.
+    fn filter_positions<P>(&self, predicate: P) -> Vec<(usize, usize)>
+    where
+        P: Fn(T) -> bool,
+    {
+        self.into_iter()
+            .filter_map(|(row, col, e)| predicate(e).then_some((row, col)))
+            .collect()
+    }
+
+    /// Returns a copy of `self` where every occurrence of `from` is replaced by `to`.
+    fn replace(&self, from: &T, to: &T) -> Self {
+        self.map(|e| if e == *from { to.clone() } else { e })
+    }
+
+    /// Returns the portion of `self` spanning `rows` and `cols`.
+    fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Self {
+        let width = cols.len();
+        let blob = self
+            .rows()
+            .into_iter()
+            .skip(rows.start)
+            .take(rows.len())
+            .flat_map(|r| r[cols.clone()].to_vec())
+            .collect();
+        Self { blob, width }
+    }
+
+    /// Returns the portion of `self` making up quadrant `q`.
+    fn quadrant(&self, q: Quadrant) -> Self {
+        let (half_height, half_width) = (self.height() / 2, self.width / 2);
+        let top = 0..half_height;
+        let bottom = self.height() - half_height..self.height();
+        let left = 0..half_width;
+        let right = self.width - half_width..self.width;
+
+        match q {
+            Quadrant::TopLeft => self.submatrix(top, left),
+            Quadrant::TopRight => self.submatrix(top, right),
+            Quadrant::BottomLeft => self.submatrix(bottom, left),
+            Quadrant::BottomRight => self.submatrix(bottom, right),
+        }
+    }
+
+    /// Returns the element at the center of `self`.
+    ///
+    /// For even dimensions, the center is the element right after the midpoint.
+    fn center(&self) -> T {
+        let (y, x) = self.center_coord();
+        self.blob[y * self.width + x].clone()
+    }
+
+    /// Returns the `(row, col)` coordinates of the center of `self`.
+    fn center_coord(&self) -> (usize, usize) {
+        (self.height() / 2, self.width / 2)
+    }
+
+    /// Returns the (2*`radius`+1)*(2*`radius`+1) portion of `self` around its center.
+    ///
+    /// If the portion does not fit in `self`, `None` is returned.
+    fn center_region(&self, radius: usize) -> Option<Self> {
+        let (y, x) = self.center_coord();
+        if radius > y || radius > x || y + radius >= self.height() || x + radius >= self.width {
+            return None;
+        }
+
+        Some(self.submatrix(y - radius..y + radius + 1, x - radius..x + radius + 1))
+    }
+
+    /// Returns the rows in `self`.
+    fn rows(&self) -> Vec<Vec<T>> {
+        self.blob
+            .chunks_exact(self.width)
+            .map(<[T]>::to_vec)
+            .collect()
+    }
+
+    /// Returns the columns in `self`.
+    fn cols(&self) -> Vec<Vec<T>> {
+        let mut cols = Vec::with_capacity(self.width);
+
+        for col_idx in 0..self.width {
+            // Skip to the first entry in a column, then collect it.
+            let col: Vec<T> = self
+                .blob
+                .iter()
+                .skip(col_idx)
+                .step_by(self.width)
+                .cloned()
+                .collect();
+            cols.push(col);
+        }
+        cols
+    }
+
+    /// Returns the right-to-left diagonals in `self`, each read from top to bottom.
+    ///
+    /// Diagonals start from the top left corner, growing by one element at a time up to the
+    /// longest ones and then shrinking down to the bottom right corner.
+    fn anti_diagonals(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        let (height, width) = (self.height(), self.width);
+        (0..(height + width).saturating_sub(1)).map(move |sum| {
+            let first_row = sum.saturating_sub(width - 1);
+            let last_row = sum.min(height - 1);
+            (first_row..=last_row)
+                .map(|r| self[(r, sum - r)].clone())
+                .collect()
+        })
+    }
+
+    /// Returns the diagonal going from the top right corner of `self` towards the bottom left.
+    fn main_anti_diagonal(&self) -> Vec<T> {
+        (0..self.height().min(self.width))
+            .map(|r| self[(r, self.width - 1 - r)].clone())
+            .collect()
+    }
+
+    /// Returns the elements around `(row, col)` and their coordinates, diagonals included.
+    ///
+    /// Neighbors outside of `self` are omitted.
+    fn neighborhood(&self, row: usize, col: usize) -> Vec<(usize, usize, T)> {
+        #[rustfmt::skip]
+        let offsets = [
+            (-1, -1), (-1, 0), (-1, 1),
+            ( 0, -1),          ( 0, 1),
+            ( 1, -1), ( 1, 0), ( 1, 1),
+        ];
+        self.neighbors_at(row, col, &offsets)
+    }
+
+    /// Returns the elements above, below, left and right of `(row, col)` and their coordinates.
+    ///
+    /// Neighbors outside of `self` are omitted.
+    fn von_neumann_neighborhood(&self, row: usize, col: usize) -> Vec<(usize, usize, T)> {
+        self.neighbors_at(row, col, &[(-1, 0), (0, -1), (0, 1), (1, 0)])
+    }
+
+    /// Returns the elements at each of `offsets` from `(row, col)` that lie within `self`.
+    fn neighbors_at(
+        &self,
+        row: usize,
+        col: usize,
+        offsets: &[(isize, isize)],
+    ) -> Vec<(usize, usize, T)> {
+        offsets
+            .iter()
+            .filter_map(|&(dr, dc)| {
+                let r = row.checked_add_signed(dr).filter(|&r| r < self.height())?;
+                let c = col.checked_add_signed(dc).filter(|&c| c < self.width)?;
+                Some((r, c, self[(r, c)].clone()))
+            })
+            .collect()
+    }
+
+    /// Returns a copy of `self` with rows and columns swapped.
+    fn transpose(&self) -> Self {
+        Self {
+            blob: self.cols().into_iter().flatten().collect(),
+            width: self.height(),
+        }
+    }
+
+    /// Returns a copy of `self` with the elements of each row in reverse order.
+    fn reverse_rows(&self) -> Self {
+        Self {
+            blob: self
+                .rows()
+                .into_iter()
+                .flat_map(|r| r.into_iter().rev())
+                .collect(),
+            width: self.width,
+        }
+    }
+
+    /// Checks if `self` equals its transpose.
+    fn is_symmetric(&self) -> bool {
+        *self == self.transpose()
+    }
+
+    /// Checks if `self` equals its horizontal flip, so that every row is a palindrome.
+    fn is_antisymmetric_rows(&self) -> bool {
+        *self == self.reverse_rows()
+    }
+
+    /// Counts the elements at `(row, col)` equal to the element at `(col, row)`.
+    ///
+    /// Elements with no counterpart, which only happens in non-square matrices, are not counted.
+    fn symmetry_score(&self) -> usize {
+        (0..self.height())
+            .flat_map(|r| (0..self.width).map(move |c| (r, c)))
+            .filter(|&(r, c)| c < self.height() && r < self.width && self[(r, c)] == self[(c, r)])
+            .count()
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        assert!(col < self.width, "column {col} out of bounds");
+        &self.blob[row * self.width + col]
+    }
+}
+
+/// An iterator over the elements of a matrix and their `(row, col)` coordinates, in row-major
+/// order.
+struct MatrixIter<'a, T> {
+    elements: Enumerate<slice::Iter<'a, T>>,
     width: usize,
 }
 
-impl SquareMatrix {
-    /// Creates a new `SquareMatrix` from the data in `blob`.
+impl<T: Clone> Iterator for MatrixIter<'_, T> {
+    type Item = (usize, usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, e) = self.elements.next()?;
+        Some((i / self.width, i % self.width, e.clone()))
+    }
+}
+
+/// An iterator over mutable references to the elements of a matrix and their `(row, col)`
+/// coordinates, in row-major order.
+struct MatrixIterMut<'a, T> {
+    elements: Enumerate<slice::IterMut<'a, T>>,
+    width: usize,
+}
+
+impl<'a, T> Iterator for MatrixIterMut<'a, T> {
+    type Item = (usize, usize, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, e) = self.elements.next()?;
+        Some((i / self.width, i % self.width, e))
+    }
+}
+
+/// An iterator moving the elements out of a matrix alongside their `(row, col)` coordinates, in
+/// row-major order.
+struct MatrixIntoIter<T> {
+    elements: Enumerate<vec::IntoIter<T>>,
+    width: usize,
+}
+
+impl<T> Iterator for MatrixIntoIter<T> {
+    type Item = (usize, usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, e) = self.elements.next()?;
+        Some((i / self.width, i % self.width, e))
+    }
+}
+
+impl<'a, T: Clone> IntoIterator for &'a Matrix<T> {
+    type Item = (usize, usize, T);
+    type IntoIter = MatrixIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MatrixIter {
+            elements: self.blob.iter().enumerate(),
+            width: self.width,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Matrix<T> {
+    type Item = (usize, usize, &'a mut T);
+    type IntoIter = MatrixIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MatrixIterMut {
+            elements: self.blob.iter_mut().enumerate(),
+            width: self.width,
+        }
+    }
+}
+
+impl<T> IntoIterator for Matrix<T> {
+    type Item = (usize, usize, T);
+    type IntoIter = MatrixIntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MatrixIntoIter {
+            elements: self.blob.into_iter().enumerate(),
+            width: self.width,
+        }
+    }
+}
+
+impl TryFrom<&str> for CharMatrix {
+    type Error = MatrixError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let blob: Vec<char> = s.chars().filter(|&c| c != '\n' && c != '\r').collect();
+        Self::new(&blob)
+    }
+}
+
+impl TryFrom<String> for CharMatrix {
+    type Error = MatrixError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::try_from(s.as_str())
+    }
+}
+
+impl CharMatrix {
+    /// Creates a new n*n `CharMatrix` from the data in `blob`.
     ///
-    /// The square root of `blob`'s `.len()` must be an integer.
-    fn new(blob: &[char]) -> Result<Self, &'static str> {
+    /// # Errors
+    ///
+    /// Returns an error if `blob` is empty or the square root of its `.len()` is not an integer.
+    fn new(blob: &[char]) -> Result<Self, MatrixError> {
         // Pretty hacky, but passing correct data is on the caller.
         #[allow(clippy::cast_precision_loss)]
         let width = (blob.len() as f64).sqrt();
         if width.fract() != 0.0 {
-            return Err("invalid matrix shape");
+            return Err(MatrixError::NonSquare {
+                len: blob.len(),
+                sqrt: width,
+            });
         }
         #[allow(clippy::cast_possible_truncation)]
         #[allow(clippy::cast_sign_loss)]
         let width = width as usize;
 
-        let blob = blob.to_vec();
+        Self::new_with_width(blob, width)
+    }
+
+    /// Creates a new `width`*`width` `CharMatrix` where every element is `value`.
+    fn new_filled(width: usize, value: char) -> Self {
+        Self::new_filled_with(width, |_, _| value)
+    }
+
+    /// Creates a new `width`*`width` `CharMatrix` where the element at `(row, col)` is
+    /// `f(row, col)`.
+    fn new_filled_with<F>(width: usize, f: F) -> Self
+    where
+        F: Fn(usize, usize) -> char,
+    {
+        let blob = (0..width)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| f(row, col))
+            .collect();
+        Self { blob, width }
+    }
+
+    /// Encodes `self` as its width, in 4 little-endian bytes, followed by one byte per character.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` contains non-ASCII characters or its width does not fit in 4 bytes.
+    fn to_bytes(&self) -> Vec<u8> {
+        let width = u32::try_from(self.width).expect("width should fit in 4 bytes");
+        let mut bytes = Vec::with_capacity(4 + self.blob.len());
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(self.blob.iter().map(|&c| {
+            assert!(c.is_ascii(), "character {c:?} is not ASCII");
+            c as u8
+        }));
+        bytes
+    }
+
+    /// Decodes a `CharMatrix` encoded by `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` is truncated, contains non-ASCII characters or cannot be split
+    /// in rows of the encoded width.
+    fn from_bytes(data: &[u8]) -> Result<Self, ByteDecodeError> {
+        let (width, body) = data.split_first_chunk().ok_or(ByteDecodeError::TooShort)?;
+        let width =
+            usize::try_from(u32::from_le_bytes(*width)).expect("width should fit in a usize");
+
+        if let Some(i) = body.iter().position(|b| !b.is_ascii()) {
+            return Err(ByteDecodeError::NonAscii(body[i], i + 4));
+        }
+        if body.is_empty() || width == 0 || !body.len().is_multiple_of(width) {
+            return Err(ByteDecodeError::LengthMismatch {
+                len: body.len(),
+                width,
+            });
+        }
+
+        let blob = body.iter().map(|&b| char::from(b)).collect();
         Ok(Self { blob, width })
     }
 
+    /// Wraps `self` in an `Arc`, so that it can be searched from several threads without copies.
+    fn into_arc(self) -> Arc<SquareMatrix> {
+        Arc::new(self)
+    }
+
+    /// Counts the occurrences of each of `needles` in `self`, searching for them in parallel.
+    ///
+    /// Counts are returned in the same order as `needles`.
+    fn count_in_matrix_parallel(&self, needles: &[&[char]]) -> Vec<usize> {
+        needles
+            .par_iter()
+            .map(|needle| self.count_in_matrix(needle))
+            .collect()
+    }
+
     fn count_in_matrix(&self, needle: &[char]) -> usize {
-        self.count(&Orientation::Rows, needle)
-            + self.count(&Orientation::Columns, needle)
-            + self.count_in_diagonals(&Direction::LeftToRight, needle)
-            + self.count_in_diagonals(&Direction::RightToLeft, needle)
+        self.count_in_matrix_mode(needle, MatchMode::Either)
+    }
+
+    /// Counts the occurrences of `needle` in `self` that read left to right in rows and top to
+    /// bottom in columns and diagonals.
+    fn count_forward_only(&self, needle: &[char]) -> usize {
+        self.count_in_matrix_mode(needle, MatchMode::Forward)
+    }
+
+    /// Counts the occurrences of `needle` in `self` matching according to `mode`.
+    fn count_in_matrix_mode(&self, needle: &[char], mode: MatchMode) -> usize {
+        self.count_mode(&Orientation::Rows, needle, mode)
+            + self.count_mode(&Orientation::Columns, needle, mode)
+            + self.count_in_diagonals_mode(&Direction::LeftToRight, needle, mode)
+            + self.count_in_diagonals_mode(&Direction::RightToLeft, needle, mode)
+    }
+
+    /// Returns the words of `len` chars starting at `(row, col)` in all 8 directions.
+    ///
+    /// Directions that would leave `self` before the word is complete are omitted.
+    fn words_starting_at(&self, row: usize, col: usize, len: usize) -> Vec<Vec<char>> {
+        const DIRECTIONS: [(isize, isize); 8] = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+
+        DIRECTIONS
+            .iter()
+            .filter_map(|&(dr, dc)| {
+                (0..len)
+                    .map(|i| {
+                        let i = isize::try_from(i).ok()?;
+                        let r = row.checked_add_signed(dr * i)?;
+                        let c = col.checked_add_signed(dc * i)?;
+                        (r < self.height() && c < self.width).then(|| self.blob[r * self.width + c])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Counts the occurrences of `needle` lying entirely within quadrant `q` of `self`.
+    fn count_in_quadrant(&self, q: Quadrant, needle: &[char]) -> usize {
+        self.quadrant(q).count_in_matrix(needle)
     }
 
     /// Counts the occurrences of `needle` in self's rows or columns.
     ///
     /// Matches will also be counted if `needle` matches backwards.
     fn count(&self, orientation: &Orientation, needle: &[char]) -> usize {
+        self.count_mode(orientation, needle, MatchMode::Either)
+    }
+
+    /// Counts the occurrences of `needle` in self's rows or columns, matching according to `mode`.
+    fn count_mode(&self, orientation: &Orientation, needle: &[char], mode: MatchMode) -> usize {
         let haystack = match orientation {
             Orientation::Rows => self.rows(),
             Orientation::Columns => self.cols(),
@@ -62,7 +648,7 @@ impl SquareMatrix {
         let mut matches = 0;
         for h in haystack {
             for w in h.windows(needle.len()) {
-                if slices_match(w, needle) {
+                if slices_match_mode(w, needle, mode) {
                     matches += 1;
                 }
             }
@@ -75,9 +661,26 @@ impl SquareMatrix {
     ///
     /// Matches will also be counted if `needle` matches backwards.
     fn count_in_diagonals(&self, direction: &Direction, needle: &[char]) -> usize {
+        self.count_in_diagonals_mode(direction, needle, MatchMode::Either)
+    }
+
+    /// Counts the occurrences of `needle` in self's diagonals following `direction`, matching
+    /// according to `mode`.
+    ///
+    /// Diagonals are read from top to bottom.
+    fn count_in_diagonals_mode(
+        &self,
+        direction: &Direction,
+        needle: &[char],
+        mode: MatchMode,
+    ) -> usize {
+        if needle.len() > self.width || needle.len() > self.height() {
+            return 0;
+        }
+
         let rows = self.rows();
 
-        let row_range = 0..=(self.width - needle.len());
+        let row_range = 0..=(self.height() - needle.len());
         let col_range = match direction {
             Direction::LeftToRight => 0..self.width + 1 - needle.len(),
             Direction::RightToLeft => needle.len() - 1..self.width,
@@ -87,7 +690,7 @@ impl SquareMatrix {
         for y in row_range {
             for x in col_range.clone() {
                 let w = get_diagonal(&rows[y..y + needle.len()], x, direction);
-                if slices_match(&w, needle) {
+                if slices_match_mode(&w, needle, mode) {
                     matches += 1;
                 }
             }
@@ -101,15 +704,18 @@ impl SquareMatrix {
     /// # Errors
     ///
     /// Returns an error if `needle`'s length is less than 3 or an even number.
-    fn count_intersections(&self, needle: &[char]) -> Result<usize, &'static str> {
-        if needle.len() < 3 || needle.len() % 2 == 0 {
-            return Err("invalid needle length");
+    fn count_intersections(&self, needle: &[char]) -> Result<usize, SearchError> {
+        if needle.len() < 3 {
+            return Err(SearchError::NeedleTooShort(needle.len()));
+        }
+        if needle.len().is_multiple_of(2) {
+            return Err(SearchError::EvenNeedleLength(needle.len()));
         }
 
         let midpoint = needle.len() / 2;
 
         let rows = self.rows();
-        let row_range = needle[..midpoint].len()..self.width - needle[midpoint + 1..].len();
+        let row_range = needle[..midpoint].len()..self.height() - needle[midpoint + 1..].len();
         let col_range = midpoint..self.width - midpoint;
 
         let mut matches = 0;
@@ -135,30 +741,102 @@ impl SquareMatrix {
         Ok(matches)
     }
 
-    /// Returns the rows in `self`.
-    fn rows(&self) -> Vec<Vec<char>> {
-        self.blob
-            .chunks_exact(self.width)
-            .map(<[char]>::to_vec)
-            .collect()
-    }
+    /// Counts the occurrences of two diagonal `needle`s of even length that cross between their
+    /// two middle elements.
+    ///
+    /// An even-length needle has no midpoint to share, so two of them intersect when they are the
+    /// diagonals of the same n*n square: the elements at `needle.len() / 2 - 1` and
+    /// `needle.len() / 2` of each needle lie on either side of the square's center, which is the
+    /// corner shared by its four central elements. Like `count_intersections`, needles are also
+    /// matched backwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `needle`'s length is less than 2 or an odd number.
+    fn count_intersections_even(&self, needle: &[char]) -> Result<usize, SearchError> {
+        if needle.len() < 2 {
+            return Err(SearchError::NeedleTooShort(needle.len()));
+        }
+        if !needle.len().is_multiple_of(2) {
+            return Err(SearchError::OddNeedleLength(needle.len()));
+        }
+        if needle.len() > self.width || needle.len() > self.height() {
+            return Ok(0);
+        }
 
-    /// Returns the columns in `self`.
-    fn cols(&self) -> Vec<Vec<char>> {
-        let mut cols = Vec::with_capacity(self.width);
+        let last = needle.len() - 1;
+        let rows = self.rows();
 
-        for row_idx in 0..self.width {
-            // Skip to the first entry in a column, then collect it.
-            let col: Vec<char> = self
-                .blob
+        let mut matches = 0;
+        for y in 0..=self.height() - needle.len() {
+            for x in 0..=self.width - needle.len() {
+                let rows = &rows[y..=y + last];
+                let ltr_diag = get_diagonal(rows, x, &Direction::LeftToRight);
+                if !slices_match(&ltr_diag, needle) {
+                    continue;
+                }
+
+                let rtl_diag = get_diagonal(rows, x + last, &Direction::RightToLeft);
+                if slices_match(&rtl_diag, needle) {
+                    matches += 1;
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Returns a copy of `self` without the rows and columns made up only of `empty`.
+    ///
+    /// The indices of the rows and columns that were kept are returned alongside the new matrix,
+    /// so that positions in it can be mapped back to `self`.
+    fn compact(&self, empty: char) -> (CharMatrix, Vec<usize>, Vec<usize>) {
+        let kept = |lines: Vec<Vec<char>>| -> Vec<usize> {
+            lines
                 .iter()
-                .skip(row_idx)
-                .step_by(self.width)
-                .copied()
-                .collect();
-            cols.push(col);
+                .enumerate()
+                .filter(|(_, l)| l.iter().any(|&c| c != empty))
+                .map(|(i, _)| i)
+                .collect()
+        };
+        let kept_rows = kept(self.rows());
+        let kept_cols = kept(self.cols());
+
+        let mut blob = Vec::with_capacity(kept_rows.len() * kept_cols.len());
+        for &y in &kept_rows {
+            blob.extend(kept_cols.iter().map(|&x| self.blob[y * self.width + x]));
         }
-        cols
+
+        let matrix = Matrix {
+            blob,
+            width: kept_cols.len(),
+        };
+        (matrix, kept_rows, kept_cols)
+    }
+
+    /// Encodes the elements of `self` as runs of identical chars, paired with their length.
+    fn encode(&self) -> Vec<(char, u32)> {
+        let mut runs: Vec<(char, u32)> = vec![];
+        for &c in &self.blob {
+            match runs.last_mut() {
+                Some((last, n)) if *last == c => *n += 1,
+                _ => runs.push((c, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Creates a new `CharMatrix` from the runs in `encoded`, split in rows of `width` elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the decoded data is empty or its length is not a multiple of `width`.
+    fn decode(encoded: &[(char, u32)], width: usize) -> Result<Self, MatrixError> {
+        let blob: Vec<char> = encoded
+            .iter()
+            .flat_map(|&(c, n)| std::iter::repeat_n(c, n as usize))
+            .collect();
+        Self::new_with_width(&blob, width)
     }
 }
 
@@ -178,19 +856,24 @@ fn get_diagonal(rows: &[Vec<char>], start: usize, direction: &Direction) -> Vec<
 
 /// Determines if `first` matches `second`, either normally or backwards.
 fn slices_match(first: &[char], second: &[char]) -> bool {
-    if first == second {
-        return true;
+    slices_match_mode(first, second, MatchMode::Either)
+}
+
+/// Determines if `first` matches `second` in the directions allowed by `mode`.
+fn slices_match_mode<T: PartialEq>(first: &[T], second: &[T], mode: MatchMode) -> bool {
+    let forward = || first == second;
+    let backward = || first.len() == second.len() && first.iter().eq(second.iter().rev());
+    match mode {
+        MatchMode::Forward => forward(),
+        MatchMode::Backward => backward(),
+        MatchMode::Either => forward() || backward(),
     }
-    first.iter().zip(second.iter().rev()).all(|(a, b)| a == b)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
-    let data = fs::read_to_string(dataset)?
-        .chars()
-        .filter(|&c| c != '\n')
-        .collect::<Vec<_>>();
-    let matrix = SquareMatrix::new(&data)?;
+    let data = fs::read_to_string(dataset)?;
+    let matrix = SquareMatrix::try_from(data.as_str())?;
 
     let needle = "XMAS".chars().collect::<Vec<_>>();
     println!("Occurrences in matrix: {}", matrix.count_in_matrix(&needle));
@@ -204,6 +887,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::thread;
 
     fn get_test_data() -> Vec<char> {
         let test_data = vec![
@@ -253,6 +937,115 @@ mod tests {
         assert_eq!(sm.count_in_diagonals(&Direction::RightToLeft, &needle), 5);
     }
 
+    #[test]
+    fn square_matrix_is_encoded_as_bytes() {
+        let sm = SquareMatrix::new(&['X', 'M', 'A', 'S']).unwrap();
+        assert_eq!(sm.to_bytes(), [2, 0, 0, 0, b'X', b'M', b'A', b'S']);
+
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let bytes = sm.to_bytes();
+        assert_eq!(bytes.len(), 4 + 100);
+        assert_eq!(SquareMatrix::from_bytes(&bytes).unwrap(), sm);
+
+        let sm = CharMatrix::new_with_width(&['a', 'b', 'c', 'd', 'e', 'f'], 3).unwrap();
+        assert_eq!(SquareMatrix::from_bytes(&sm.to_bytes()).unwrap(), sm);
+    }
+
+    #[test]
+    fn square_matrix_rejects_bad_bytes() {
+        assert_eq!(
+            SquareMatrix::from_bytes(&[2, 0, 0]),
+            Err(ByteDecodeError::TooShort)
+        );
+        assert_eq!(
+            SquareMatrix::from_bytes(&[2, 0, 0, 0, b'X', 0xc3, b'A', b'S']),
+            Err(ByteDecodeError::NonAscii(0xc3, 5))
+        );
+        assert_eq!(
+            SquareMatrix::from_bytes(&[2, 0, 0, 0, b'X', b'M', b'A']),
+            Err(ByteDecodeError::LengthMismatch { len: 3, width: 2 })
+        );
+        assert_eq!(
+            SquareMatrix::from_bytes(&[2, 0, 0, 0]),
+            Err(ByteDecodeError::LengthMismatch { len: 0, width: 2 })
+        );
+        assert_eq!(
+            SquareMatrix::from_bytes(&[0, 0, 0, 0, b'X']),
+            Err(ByteDecodeError::LengthMismatch { len: 1, width: 0 })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is not ASCII")]
+    fn square_matrix_encoding_panics_on_non_ascii() {
+        SquareMatrix::new(&['X', 'M', 'Ä', 'S']).unwrap().to_bytes();
+    }
+
+    #[test]
+    fn square_matrix_returns_neighborhoods() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+
+        assert_eq!(
+            sm.neighborhood(0, 0),
+            [(0, 1, 'M'), (1, 0, 'M'), (1, 1, 'S')]
+        );
+        assert_eq!(sm.neighborhood(0, 9).len(), 3);
+        assert_eq!(sm.neighborhood(9, 9).len(), 3);
+        assert_eq!(sm.neighborhood(0, 5).len(), 5);
+        assert_eq!(sm.neighborhood(5, 9).len(), 5);
+        assert_eq!(sm.neighborhood(4, 4).len(), 8);
+
+        assert_eq!(sm.von_neumann_neighborhood(0, 0).len(), 2);
+        assert_eq!(sm.von_neumann_neighborhood(0, 5).len(), 3);
+        assert_eq!(
+            sm.von_neumann_neighborhood(4, 4),
+            [(3, 4, 'A'), (4, 3, 'S'), (4, 5, 'M'), (5, 4, 'M')]
+        );
+
+        for (row, col) in [(0, 0), (0, 5), (4, 4), (9, 9)] {
+            for (r, c, e) in sm.neighborhood(row, col) {
+                assert!(r < sm.height() && c < sm.width);
+                assert!(r.abs_diff(row) <= 1 && c.abs_diff(col) <= 1);
+                assert_ne!((r, c), (row, col));
+                assert_eq!(e, sm[(r, c)]);
+            }
+        }
+    }
+
+    #[test]
+    fn square_matrix_enumerates_anti_diagonals() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let needle: Vec<char> = "XMAS".chars().collect();
+
+        assert_eq!(
+            sm.main_anti_diagonal().iter().collect::<String>(),
+            "MSAMMMMXAM"
+        );
+
+        let diagonals: Vec<_> = sm.anti_diagonals().collect();
+        assert_eq!(diagonals.len(), 2 * sm.width - 1);
+        let lengths: Vec<_> = diagonals.iter().map(Vec::len).collect();
+        assert_eq!(
+            lengths,
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1]
+        );
+        assert_eq!(diagonals[sm.width - 1], sm.main_anti_diagonal());
+
+        let matches: usize = sm
+            .anti_diagonals()
+            .map(|d| {
+                d.windows(needle.len())
+                    .filter(|w| slices_match(w, &needle))
+                    .count()
+            })
+            .sum();
+        assert_eq!(matches, 5);
+        assert_eq!(
+            matches,
+            sm.count_in_diagonals(&Direction::RightToLeft, &needle)
+        );
+    }
+
     #[test]
     fn square_matrix_finds_needle_in_self() {
         let sm = SquareMatrix::new(&get_test_data()).unwrap();
@@ -261,6 +1054,59 @@ mod tests {
         assert_eq!(sm.count_in_matrix(&needle), 18);
     }
 
+    #[test]
+    fn square_matrix_finds_needle_by_direction() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let xmas: Vec<char> = "XMAS".chars().collect();
+        let samx: Vec<char> = "SAMX".chars().collect();
+
+        let forward = sm.count_in_matrix_mode(&xmas, MatchMode::Forward);
+        let backward = sm.count_in_matrix_mode(&xmas, MatchMode::Backward);
+        assert_eq!(sm.count_forward_only(&xmas), forward);
+        assert_eq!(sm.count_forward_only(&samx), backward);
+        assert_eq!(forward + backward, sm.count_in_matrix(&xmas));
+        assert_eq!(sm.count_in_matrix_mode(&xmas, MatchMode::Either), 18);
+
+        assert!(slices_match_mode(&xmas, &xmas, MatchMode::Forward));
+        assert!(!slices_match_mode(&samx, &xmas, MatchMode::Forward));
+        assert!(slices_match_mode(&samx, &xmas, MatchMode::Backward));
+        assert!(!slices_match_mode(&xmas, &xmas, MatchMode::Backward));
+        assert!(slices_match_mode(&xmas, &xmas, MatchMode::Either));
+        assert!(slices_match_mode(&samx, &xmas, MatchMode::Either));
+        assert!(!slices_match_mode(&[1, 2], &[2, 1, 0], MatchMode::Either));
+    }
+
+    #[test]
+    fn square_matrix_finds_needles_in_parallel() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let xmas: Vec<char> = "XMAS".chars().collect();
+        let mas: Vec<char> = "MAS".chars().collect();
+
+        assert_eq!(
+            sm.count_in_matrix_parallel(&[&xmas]),
+            [sm.count_in_matrix(&xmas)]
+        );
+        assert_eq!(
+            sm.count_in_matrix_parallel(&[&xmas, &mas]),
+            [sm.count_in_matrix(&xmas), sm.count_in_matrix(&mas)]
+        );
+        assert!(sm.count_in_matrix_parallel(&[]).is_empty());
+
+        let shared = sm.into_arc();
+        let handles: Vec<_> = [xmas, mas]
+            .into_iter()
+            .map(|needle| {
+                let sm = Arc::clone(&shared);
+                thread::spawn(move || sm.count_in_matrix(&needle))
+            })
+            .collect();
+        let counts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(
+            shared.count_in_matrix_parallel(&[&['X', 'M', 'A', 'S'], &['M', 'A', 'S']]),
+            counts
+        );
+    }
+
     #[test]
     fn square_matrix_finds_intersected_needle_in_self() {
         let sm = SquareMatrix::new(&get_test_data()).unwrap();
@@ -268,4 +1114,346 @@ mod tests {
 
         assert_eq!(sm.count_intersections(&needle).unwrap(), 9);
     }
+
+    #[test]
+    fn char_matrix_accepts_rectangular_data() {
+        let data: Vec<char> = get_test_data().into_iter().take(50).collect();
+        let cm = CharMatrix::new_with_width(&data, 10).unwrap();
+        assert_eq!(cm.height(), 5);
+        assert_eq!(cm.rows().len(), 5);
+        assert_eq!(cm.cols().len(), 10);
+        assert_eq!(cm.cols()[0], vec!['M', 'M', 'A', 'M', 'X']);
+
+        assert!(SquareMatrix::new(&data).is_err());
+        assert_eq!(SquareMatrix::new(&get_test_data()).unwrap().width, 10);
+    }
+
+    #[test]
+    fn char_matrix_compacts_empty_lines() {
+        let data: Vec<char> = ["XSM.", "....", "A.S.", "M..."]
+            .into_iter()
+            .flat_map(str::chars)
+            .collect();
+        let cm = CharMatrix::new(&data).unwrap();
+
+        let (compact, rows, cols) = cm.compact('.');
+        assert_eq!(compact.height(), cm.height() - 1);
+        assert_eq!(compact.width, cm.width - 1);
+        assert_eq!(rows, [0, 2, 3]);
+        assert_eq!(cols, [0, 1, 2]);
+        for (y, row) in compact.rows().iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                assert_eq!(*c, cm.blob[rows[y] * cm.width + cols[x]]);
+            }
+        }
+
+        let cm = SquareMatrix::new(&get_test_data()).unwrap();
+        let (compact, rows, cols) = cm.compact('.');
+        assert_eq!(compact, cm);
+        assert_eq!(rows, (0..10).collect::<Vec<_>>());
+        assert_eq!(cols, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn char_matrix_maps_elements() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let lower = sm.map(|c| c.to_ascii_lowercase());
+        assert!(lower.blob.iter().all(char::is_ascii_lowercase));
+
+        let upper = lower.map(|c| c.to_uppercase().next().unwrap());
+        assert_eq!(upper, sm);
+
+        let flags = sm.map(|c| c == 'X');
+        assert!(flags.rows()[0][4]);
+    }
+
+    #[test]
+    fn square_matrix_is_filled() {
+        let sm = SquareMatrix::new_filled(5, '.');
+        assert_eq!(sm.blob.len(), 25);
+        assert!(sm.blob.iter().all(|&x| x == '.'));
+        assert_eq!(sm.height(), 5);
+
+        let echo = |r: usize, _| char::from_digit(u32::try_from(r).unwrap(), 10).unwrap();
+        let sm = SquareMatrix::new_filled_with(3, echo);
+        assert_eq!(sm.rows(), [['0'; 3], ['1'; 3], ['2'; 3]]);
+
+        let checkers =
+            SquareMatrix::new_filled_with(
+                4,
+                |r, c| {
+                    if (r + c).is_multiple_of(2) {
+                        '.'
+                    } else {
+                        '#'
+                    }
+                },
+            );
+        assert_eq!(checkers.filter_positions(|c| c == '#').len(), 8);
+        assert!(!checkers.is_antisymmetric_rows());
+        assert!(checkers.is_symmetric());
+    }
+
+    #[test]
+    fn square_matrix_iterates_with_coordinates() {
+        let data = get_test_data();
+        let sm = SquareMatrix::new(&data).unwrap();
+
+        let cells: Vec<_> = (&sm).into_iter().collect();
+        assert_eq!(cells.len(), sm.width * sm.width);
+        for (i, &(row, col, c)) in cells.iter().enumerate() {
+            assert_eq!((row, col), (i / sm.width, i % sm.width));
+            assert_eq!(c, data[i]);
+        }
+        assert_eq!(sm.clone().into_iter().collect::<Vec<_>>(), cells);
+
+        let mut sm = sm;
+        for (row, col, c) in &mut sm {
+            if row == col {
+                *c = '#';
+            }
+        }
+        let diagonal: Vec<_> = (0..sm.width).map(|i| (i, i)).collect();
+        assert_eq!(sm.filter_positions(|c| c == '#'), diagonal);
+    }
+
+    #[test]
+    fn char_matrix_filters_positions() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let xs = sm.filter_positions(|c| c == 'X');
+
+        assert_eq!(xs.len(), 19);
+        assert_eq!(xs[0], (0, 4));
+        assert!(xs.iter().all(|&(y, x)| sm.rows()[y][x] == 'X'));
+    }
+
+    #[test]
+    fn char_matrix_replaces_elements() {
+        let data: Vec<char> = "#..#.#..#".chars().collect();
+        let cm = SquareMatrix::new(&data).unwrap();
+        let cleared = cm.replace(&'#', &'.');
+
+        assert!(cleared.filter_positions(|c| c == '#').is_empty());
+        assert_eq!(cleared.filter_positions(|c| c == '.').len(), 9);
+    }
+
+    #[test]
+    fn square_matrix_is_built_from_strings() {
+        let s = "MMMS\nMSAM\r\nAMXS\nMSAM\n";
+        let expected = SquareMatrix::new(&"MMMSMSAMAMXSMSAM".chars().collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(SquareMatrix::try_from(s).unwrap(), expected);
+        assert_eq!(SquareMatrix::try_from(s.to_string()).unwrap(), expected);
+        assert!(SquareMatrix::try_from("").is_err());
+    }
+
+    #[test]
+    fn square_matrix_finds_needle_in_quadrants() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let needle: Vec<char> = "XMAS".chars().collect();
+        let quadrants = [
+            Quadrant::TopLeft,
+            Quadrant::TopRight,
+            Quadrant::BottomLeft,
+            Quadrant::BottomRight,
+        ];
+
+        // Counted by hand, as (row, col) of the X and reading direction:
+        // - top left: (1, 4) leftwards, (4, 0) rightwards;
+        // - top right: (0, 5) rightwards, (4, 6) upwards;
+        // - bottom left: (9, 1) up-right, (9, 3) up-left;
+        // - bottom right: (9, 5) up-right and rightwards, (9, 9) up-left and upwards.
+        let counts: Vec<_> = quadrants
+            .into_iter()
+            .map(|q| sm.count_in_quadrant(q, &needle))
+            .collect();
+        assert_eq!(counts, [2, 2, 2, 4]);
+
+        // The other 8 occurrences cross the middle lines.
+        assert_eq!(sm.count_in_matrix(&needle), 18);
+
+        let top_left = sm.quadrant(Quadrant::TopLeft);
+        assert_eq!(top_left.rows()[0], ['M', 'M', 'M', 'S', 'X']);
+        assert_eq!(top_left.height(), 5);
+    }
+
+    #[test]
+    fn char_matrix_quadrants_skip_middle_lines() {
+        let data: Vec<char> = "ABCDEFGHI".chars().collect();
+        let sm = SquareMatrix::new(&data).unwrap();
+
+        assert_eq!(sm.quadrant(Quadrant::TopLeft).blob, ['A']);
+        assert_eq!(sm.quadrant(Quadrant::TopRight).blob, ['C']);
+        assert_eq!(sm.quadrant(Quadrant::BottomLeft).blob, ['G']);
+        assert_eq!(sm.quadrant(Quadrant::BottomRight).blob, ['I']);
+        assert_eq!(sm.count_in_quadrant(Quadrant::TopLeft, &['A', 'B']), 0);
+    }
+
+    #[test]
+    fn square_matrix_exposes_center() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        assert_eq!(sm.center_coord(), (5, 5));
+        assert_eq!(sm.center(), 'X');
+
+        let region = sm.center_region(1).unwrap();
+        assert_eq!((region.width, region.height()), (3, 3));
+        assert_eq!(region.center(), sm.center());
+        assert_eq!(region.rows()[0], ['A', 'M', 'X']);
+        assert_eq!(sm.center_region(4).unwrap().width, 9);
+        assert!(sm.center_region(5).is_none());
+
+        let data: Vec<char> = "ABCDEFGHI".chars().collect();
+        let sm = SquareMatrix::new(&data).unwrap();
+        assert_eq!(sm.center(), 'E');
+        assert_eq!(sm.center_region(0).unwrap().blob, ['E']);
+        assert_eq!(sm.center_region(1).unwrap(), sm);
+    }
+
+    #[test]
+    fn char_matrix_rejects_invalid_shapes() {
+        assert_eq!(
+            CharMatrix::new_with_width(&get_test_data(), 7).err(),
+            Some(MatrixError::NonDivisible { len: 100, width: 7 })
+        );
+        assert_eq!(
+            CharMatrix::new_with_width(&[], 10).err(),
+            Some(MatrixError::Empty)
+        );
+        assert_eq!(SquareMatrix::new(&[]).err(), Some(MatrixError::Empty));
+
+        let data: Vec<char> = get_test_data().into_iter().take(99).collect();
+        let err = SquareMatrix::new(&data).unwrap_err();
+        assert!(matches!(err, MatrixError::NonSquare { len: 99, .. }));
+        assert_eq!(
+            err.to_string(),
+            "expected square matrix but got 99 elements (sqrt≈9.95)"
+        );
+    }
+
+    #[test]
+    fn square_matrix_lists_words_from_cell() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let xmas: Vec<char> = "XMAS".chars().collect();
+
+        let (row, col) = sm.filter_positions(|c| c == 'X')[0];
+        assert_eq!((row, col), (0, 4));
+        let words = sm.words_starting_at(row, col, 4);
+        assert_eq!(words.len(), 5);
+        assert!(words.contains(&xmas));
+
+        assert_eq!(sm.words_starting_at(0, 0, 4).len(), 3);
+        assert_eq!(sm.words_starting_at(5, 5, 4).len(), 8);
+        assert_eq!(sm.words_starting_at(5, 5, 11).len(), 0);
+
+        let total: usize = sm
+            .filter_positions(|c| c == 'X')
+            .into_iter()
+            .map(|(r, c)| {
+                sm.words_starting_at(r, c, 4)
+                    .iter()
+                    .filter(|w| **w == xmas)
+                    .count()
+            })
+            .sum();
+        assert_eq!(total, sm.count_in_matrix(&xmas));
+    }
+
+    #[test]
+    fn square_matrix_detects_symmetry() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        assert!(!sm.is_symmetric());
+        assert!(!sm.is_antisymmetric_rows());
+        assert_eq!(sm.transpose().transpose(), sm);
+        assert_eq!(sm.transpose().rows(), sm.cols());
+        assert_eq!(sm[(0, 4)], 'X');
+        let score = sm.symmetry_score();
+        assert!(score >= sm.width && score < sm.width * sm.width);
+
+        let sym = SquareMatrix::try_from("ABC\nBDE\nCEF").unwrap();
+        assert!(sym.is_symmetric());
+        assert!(!sym.is_antisymmetric_rows());
+        assert_eq!(sym.symmetry_score(), 9);
+
+        let palindromes = SquareMatrix::try_from("ABA\nCDC\nEFE").unwrap();
+        assert!(palindromes.is_antisymmetric_rows());
+        assert_eq!(palindromes.reverse_rows(), palindromes);
+        assert_eq!(palindromes.symmetry_score(), 3);
+
+        let rect = CharMatrix::new_with_width(&['A', 'B', 'C', 'D', 'E', 'F'], 3).unwrap();
+        assert_eq!(
+            rect.transpose().rows(),
+            [['A', 'D'], ['B', 'E'], ['C', 'F']]
+        );
+        assert!(!rect.is_symmetric());
+    }
+
+    #[test]
+    fn square_matrix_round_trips_through_rle() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let encoded = sm.encode();
+
+        assert!(encoded.len() < sm.width * sm.width);
+        assert_eq!(&encoded[..3], [('M', 3), ('S', 1), ('X', 2)]);
+        assert_eq!(SquareMatrix::decode(&encoded, sm.width), Ok(sm));
+
+        let sparse =
+            CharMatrix::new_with_width(&"..........#.......".chars().collect::<Vec<_>>(), 6)
+                .unwrap();
+        assert_eq!(sparse.encode(), [('.', 10), ('#', 1), ('.', 7)]);
+
+        assert_eq!(
+            SquareMatrix::decode(&encoded[1..], 10).err(),
+            Some(MatrixError::NonDivisible { len: 97, width: 10 })
+        );
+        assert_eq!(
+            SquareMatrix::decode(&[], 10).err(),
+            Some(MatrixError::Empty)
+        );
+    }
+
+    #[test]
+    fn square_matrix_rejects_invalid_needles() {
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+
+        assert_eq!(
+            sm.count_intersections(&['M', 'A']),
+            Err(SearchError::NeedleTooShort(2))
+        );
+        assert_eq!(
+            sm.count_intersections(&['X', 'M', 'A', 'S']),
+            Err(SearchError::EvenNeedleLength(4))
+        );
+        assert_eq!(
+            sm.count_intersections_even(&['X']),
+            Err(SearchError::NeedleTooShort(1))
+        );
+        assert_eq!(
+            sm.count_intersections_even(&['M', 'A', 'S']),
+            Err(SearchError::OddNeedleLength(3))
+        );
+    }
+
+    #[test]
+    fn char_matrix_counts_even_intersections() {
+        let needle: Vec<char> = "XMAS".chars().collect();
+        #[rustfmt::skip]
+        let data: Vec<char> = concat!(
+            "X..XS..S",
+            ".MM..AA.",
+            ".AA..MM.",
+            "S..SX..X",
+        )
+        .chars()
+        .collect();
+        let mut m = CharMatrix::new_with_width(&data, 8).unwrap();
+
+        // The second X reads backwards, from the bottom up.
+        assert_eq!(m.count_intersections_even(&needle), Ok(2));
+        m.blob[9] = '.';
+        assert_eq!(m.count_intersections_even(&needle), Ok(1));
+
+        let sm = SquareMatrix::new(&get_test_data()).unwrap();
+        let mas: Vec<char> = "MAS".chars().collect();
+        assert_eq!(sm.count_intersections(&mas), Ok(9));
+    }
 }
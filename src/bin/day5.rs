@@ -1,49 +1,696 @@
 #![warn(clippy::pedantic)]
+#![allow(dead_code)]
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::num::ParseIntError;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Error returned when rules are circular.
+#[derive(Debug, PartialEq)]
+struct CycleError;
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rules are circular")
+    }
+}
+
+impl Error for CycleError {}
+
+/// Possible errors when inserting a rule.
+#[derive(Debug, PartialEq)]
+enum InsertError {
+    /// A page in the rule is malformed.
+    ParseError(ParseIntError),
+    /// The rule would make the rules circular, since `existing_path` already leads from its second
+    /// page back to its first.
+    WouldCreateCycle {
+        new_rule: (u32, u32),
+        existing_path: Vec<u32>,
+    },
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ParseError(e) => write!(f, "malformed page: {e}"),
+            Self::WouldCreateCycle {
+                new_rule: (x, y),
+                existing_path,
+            } => write!(
+                f,
+                "rule {x}|{y} would create a cycle through {existing_path:?}"
+            ),
+        }
+    }
+}
+
+impl Error for InsertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ParseError(e) => Some(e),
+            Self::WouldCreateCycle { .. } => None,
+        }
+    }
+}
+
+impl From<ParseIntError> for InsertError {
+    fn from(e: ParseIntError) -> Self {
+        Self::ParseError(e)
+    }
+}
+
 /// Rules to sort page updates with.
 ///
 /// Each page X is mapped to all the pages Y that must come after it.
-#[derive(Debug)]
 struct PageRules(HashMap<u32, Vec<u32>>);
 
+impl fmt::Display for PageRules {
+    /// Formats `self` as an adjacency list, with one `X|Y1,Y2,...` line per page X.
+    ///
+    /// Lines are sorted by X, and the pages Y in each line are sorted too.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut xs: Vec<_> = self.0.keys().copied().collect();
+        xs.sort_unstable();
+        for (i, x) in xs.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let mut ys = self.0[&x].clone();
+            ys.sort_unstable();
+            let ys: Vec<String> = ys.iter().map(u32::to_string).collect();
+            write!(f, "{x}|{}", ys.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PageRules {
+    /// Formats `self` like `Display` does, so that the output does not depend on hashing.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl PartialEq for PageRules {
+    /// Compares the rules in `self` and `other`, regardless of the order they were inserted in.
+    fn eq(&self, other: &Self) -> bool {
+        self.sorted_pairs() == other.sorted_pairs()
+    }
+}
+
+impl Eq for PageRules {}
+
+/// A single rule, as stored in configuration files.
+///
+/// Rules are written as `[x, y]` pairs, but tables like `{ from = x, to = y }` are accepted too.
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RuleRepr {
+    Pair(u32, u32),
+    Table { from: u32, to: u32 },
+}
+
+/// A set of rules, as stored in configuration files.
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+struct RulesRepr {
+    rules: Vec<RuleRepr>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for PageRules {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let rules = self
+            .sorted_pairs()
+            .into_iter()
+            .map(|(x, y)| RuleRepr::Pair(x, y))
+            .collect();
+        RulesRepr { rules }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for PageRules {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = RulesRepr::deserialize(deserializer)?;
+        Ok(repr
+            .rules
+            .into_iter()
+            .map(|r| match r {
+                RuleRepr::Pair(x, y) | RuleRepr::Table { from: x, to: y } => (x, y),
+            })
+            .collect())
+    }
+}
+
+impl FromIterator<(u32, u32)> for PageRules {
+    fn from_iter<I: IntoIterator<Item = (u32, u32)>>(iter: I) -> Self {
+        let mut rules = Self::new();
+        for (x, y) in iter {
+            rules.insert_pair(x, y);
+        }
+        rules
+    }
+}
+
 impl PageRules {
     /// Returns a new `PageRules`.
     fn new() -> Self {
         Self(HashMap::new())
     }
 
+    /// Returns a new `PageRules` where each page X in `pairs` must come before its page Y.
+    fn from_pairs<I>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, u32)>,
+    {
+        pairs.into_iter().collect()
+    }
+
     /// Parses a new `rule` and inserts it in `self`.
     fn insert(&mut self, rule: &str) -> Result<(), ParseIntError> {
         let parts = rule
             .splitn(2, '|')
             .map(str::parse::<u32>)
             .collect::<Result<Vec<_>, _>>()?;
-        self.0
-            .entry(parts[0])
-            .and_modify(|v| v.push(parts[1]))
-            .or_insert(vec![parts[1]]);
+        self.insert_pair(parts[0], parts[1]);
+        Ok(())
+    }
+
+    /// Parses a new `rule` and inserts it in `self`, unless it would make the rules circular.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rule` is malformed, or if its second page must already come before
+    /// its first one. In the latter case, `self` is left untouched.
+    fn insert_checked(&mut self, rule: &str) -> Result<(), InsertError> {
+        let (x, y) = rule.split_once('|').unwrap_or((rule, ""));
+        let (x, y) = (x.parse()?, y.parse()?);
+        if let Some(existing_path) = self.path(y, x) {
+            return Err(InsertError::WouldCreateCycle {
+                new_rule: (x, y),
+                existing_path,
+            });
+        }
+
+        self.insert_pair(x, y);
         Ok(())
     }
 
+    /// Returns a sequence of pages leading from `from` to `to` through the rules in `self`, both
+    /// included.
+    ///
+    /// If `to` cannot be reached from `from`, `None` is returned.
+    fn path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        let mut parents = HashMap::from([(from, from)]);
+        let mut stack = vec![from];
+        while let Some(page) = stack.pop() {
+            if page == to {
+                let mut path = vec![to];
+                let mut page = to;
+                while page != from {
+                    page = parents[&page];
+                    path.push(page);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for &next in self.get(page).into_iter().flatten() {
+                if let Entry::Vacant(e) = parents.entry(next) {
+                    e.insert(page);
+                    stack.push(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Parses rules written as an adjacency list by `Display`, with one `X|Y1,Y2,...` line per
+    /// page X.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a page is malformed or missing.
+    fn from_adjacency_format(s: &str) -> Result<Self, ParseIntError> {
+        let mut rules = Self::new();
+        for line in s.lines().filter(|l| !l.is_empty()) {
+            let (x, ys) = line.split_once('|').unwrap_or((line, ""));
+            let x = x.parse()?;
+            for y in ys.split(',') {
+                rules.insert_pair(x, y.parse()?);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Inserts a rule requiring page `x` to come before page `y`.
+    fn insert_pair(&mut self, x: u32, y: u32) {
+        self.0.entry(x).and_modify(|v| v.push(y)).or_insert(vec![y]);
+    }
+
+    /// Removes the rule requiring page `x` to come before page `y`, returning whether it existed.
+    fn remove_rule(&mut self, x: u32, y: u32) -> bool {
+        let Some(ys) = self.0.get_mut(&x) else {
+            return false;
+        };
+
+        let len = ys.len();
+        ys.retain(|&p| p != y);
+        let removed = ys.len() != len;
+        if ys.is_empty() {
+            self.0.remove(&x);
+        }
+        removed
+    }
+
+    /// Removes every rule where `page` must come either before or after another page.
+    fn remove_page(&mut self, page: u32) {
+        self.0.remove(&page);
+        self.retain_rules(|_, y| y != page);
+    }
+
+    /// Keeps only the rules requiring a page `x` to come before a page `y` where
+    /// `predicate(x, y)` is true.
+    fn retain_rules<F>(&mut self, predicate: F)
+    where
+        F: Fn(u32, u32) -> bool,
+    {
+        self.0.retain(|&x, ys| {
+            ys.retain(|&y| predicate(x, y));
+            !ys.is_empty()
+        });
+    }
+
+    /// Returns a new `PageRules` where every rule requiring page `x` to come before page `y` is
+    /// replaced by one requiring `y` to come before `x`.
+    ///
+    /// Sorting by the inverted rules reverses the order given by `self`.
+    fn invert(&self) -> Self {
+        self.sorted_pairs()
+            .into_iter()
+            .map(|(x, y)| (y, x))
+            .collect()
+    }
+
+    /// Returns every rule in `self` as an `(x, y)` pair, sorted.
+    fn sorted_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|(&x, ys)| ys.iter().map(move |&y| (x, y)))
+            .collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Serializes `self` as JSON, in the form `{"rules":[[x,y],...]}`.
+    #[cfg(feature = "serde")]
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serializing rules should succeed")
+    }
+
+    /// Parses rules serialized as JSON by `to_json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid JSON or does not describe rules.
+    #[cfg(feature = "serde")]
+    fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Serializes `self` as TOML, in the form `rules = [[x, y], ...]`.
+    #[cfg(feature = "serde")]
+    fn to_toml(&self) -> String {
+        toml::to_string(self).expect("serializing rules should succeed")
+    }
+
+    /// Parses rules serialized as TOML, either by `to_toml` or as `[[rules]]` tables with `from`
+    /// and `to` keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid TOML or does not describe rules.
+    #[cfg(feature = "serde")]
+    fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
     /// Returns the pages that must come after a page `x`.
     fn get(&self, x: u32) -> Option<&Vec<u32>> {
         self.0.get(&x)
     }
+
+    /// Compares pages `x` and `y` according to the rules in `self`.
+    ///
+    /// Pages with no rule between them compare as equal.
+    fn compare(&self, x: u32, y: u32) -> Ordering {
+        if self.get(x).is_some_and(|ys| ys.contains(&y)) {
+            Ordering::Less
+        } else if self.get(y).is_some_and(|xs| xs.contains(&x)) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    /// Returns the total amount of rules in `self`.
+    fn rule_count(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    /// Returns every page mentioned in `self`, either before or after another page.
+    fn pages(&self) -> HashSet<u32> {
+        self.0
+            .iter()
+            .flat_map(|(x, ys)| std::iter::once(x).chain(ys))
+            .copied()
+            .collect()
+    }
+
+    /// Returns the pages that must come before at least one other page.
+    fn sources(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// Returns the pages appearing in `updates` but in none of the rules in `self`, sorted.
+    ///
+    /// Such pages compare as equal to every other page, so an update containing them may be
+    /// considered sorted only because no rule applies.
+    fn validate_completeness(&self, updates: &[Update]) -> Vec<u32> {
+        let pages = self.pages();
+        let uncovered: BTreeSet<u32> = updates
+            .iter()
+            .flat_map(|u| u.0.iter().copied())
+            .filter(|p| !pages.contains(p))
+            .collect();
+        uncovered.into_iter().collect()
+    }
+
+    /// Checks if every page in `pages` appears in at least one of the rules in `self`.
+    fn are_all_pages_covered(&self, pages: &HashSet<u32>) -> bool {
+        pages.is_subset(&self.pages())
+    }
+
+    /// Returns the pages in `self` in an order that satisfies every rule.
+    ///
+    /// Whenever more than one page could come next, the lowest one is picked. If the rules are
+    /// circular, `None` is returned.
+    fn topological_order(&self) -> Option<Vec<u32>> {
+        let pages = self.pages();
+        let mut incoming: HashMap<u32, usize> = pages.iter().map(|&p| (p, 0)).collect();
+        for y in self.0.values().flatten() {
+            *incoming.entry(*y).or_default() += 1;
+        }
+
+        let mut ready: BTreeSet<u32> = incoming
+            .iter()
+            .filter(|(_, &n)| n == 0)
+            .map(|(&p, _)| p)
+            .collect();
+        let mut order = Vec::with_capacity(pages.len());
+        while let Some(x) = ready.pop_first() {
+            order.push(x);
+            for y in self.get(x).into_iter().flatten() {
+                let n = incoming.entry(*y).or_default();
+                *n -= 1;
+                if *n == 0 {
+                    ready.insert(*y);
+                }
+            }
+        }
+
+        (order.len() == pages.len()).then_some(order)
+    }
+
+    /// Checks if the rules in `self` are circular.
+    fn has_cycle(&self) -> bool {
+        self.strongly_connected_components()
+            .iter()
+            .any(|scc| scc.len() > 1 || self.compare(scc[0], scc[0]) == Ordering::Less)
+    }
+
+    /// Splits the pages in `self` in strongly connected components, using Tarjan's algorithm.
+    ///
+    /// Components are returned in reverse topological order. Any component with more than one
+    /// page, or a single page that must come after itself, indicates circular rules.
+    fn strongly_connected_components(&self) -> Vec<Vec<u32>> {
+        let mut pages: Vec<u32> = self.pages().into_iter().collect();
+        pages.sort_unstable();
+
+        let mut tarjan = Tarjan {
+            rules: self,
+            next_index: 0,
+            indices: HashMap::with_capacity(pages.len()),
+            low_links: HashMap::with_capacity(pages.len()),
+            stack: vec![],
+            on_stack: HashSet::new(),
+            components: vec![],
+        };
+        for p in pages {
+            if !tarjan.indices.contains_key(&p) {
+                tarjan.visit(p);
+            }
+        }
+
+        tarjan.components
+    }
+
+    /// Returns the length of the longest chain of rules starting from `page`.
+    ///
+    /// A page that precedes no other page has depth 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rules are circular.
+    fn depth(&self, page: u32) -> Result<usize, CycleError> {
+        if self.has_cycle() {
+            return Err(CycleError);
+        }
+        Ok(self.depth_cached(page, &mut HashMap::new()))
+    }
+
+    /// Returns the length of the longest chain of rules in `self`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rules are circular.
+    fn max_depth(&self) -> Result<usize, CycleError> {
+        if self.has_cycle() {
+            return Err(CycleError);
+        }
+
+        let mut cache = HashMap::new();
+        Ok(self
+            .sources()
+            .map(|p| self.depth_cached(p, &mut cache))
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Returns the depth of `page`, reusing and filling the depths already in `cache`.
+    ///
+    /// The rules must not be circular.
+    fn depth_cached(&self, page: u32, cache: &mut HashMap<u32, usize>) -> usize {
+        if let Some(&d) = cache.get(&page) {
+            return d;
+        }
+
+        let d = self
+            .get(page)
+            .into_iter()
+            .flatten()
+            .map(|&y| self.depth_cached(y, cache) + 1)
+            .max()
+            .unwrap_or(0);
+        cache.insert(page, d);
+        d
+    }
+
+    /// Checks if `self` fully determines the order of its pages.
+    ///
+    /// This is the case when every pair of pages is covered by exactly one rule and the rules
+    /// are not circular, so that `topological_order` returns the only valid sequence.
+    fn is_total_order(&self) -> bool {
+        let pages: Vec<u32> = self.pages().into_iter().collect();
+        let conflicting = pages.iter().enumerate().any(|(i, &x)| {
+            pages[i + 1..].iter().any(|&y| {
+                self.compare(x, y) == Ordering::Less && self.compare(y, x) == Ordering::Less
+            })
+        });
+
+        !conflicting && self.missing_pairs().is_empty() && self.topological_order().is_some()
+    }
+
+    /// Returns the pairs of pages with no rule between them, lowest page first.
+    fn missing_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pages: Vec<u32> = self.pages().into_iter().collect();
+        pages.sort_unstable();
+
+        let mut missing = vec![];
+        for (i, &x) in pages.iter().enumerate() {
+            for &y in &pages[i + 1..] {
+                if self.compare(x, y) == Ordering::Equal {
+                    missing.push((x, y));
+                }
+            }
+        }
+        missing
+    }
+
+    /// Computes statistics about the rules in `self`.
+    fn summary_stats(&self) -> RuleStats {
+        let mut in_degrees: HashMap<u32, usize> = HashMap::new();
+        for y in self.0.values().flatten() {
+            *in_degrees.entry(*y).or_default() += 1;
+        }
+
+        let page_count = self.pages().len();
+        let rule_count = self.rule_count();
+        // Pages are few enough to be represented exactly.
+        #[allow(clippy::cast_precision_loss)]
+        let average_out_degree = if page_count == 0 {
+            0.0
+        } else {
+            rule_count as f64 / page_count as f64
+        };
+
+        RuleStats {
+            page_count,
+            rule_count,
+            max_out_degree: self.0.values().map(Vec::len).max().unwrap_or(0),
+            max_in_degree: in_degrees.into_values().max().unwrap_or(0),
+            average_out_degree,
+        }
+    }
+}
+
+/// Statistics about a set of rules.
+///
+/// The degrees of a page are the amounts of rules it appears in, either before (out-degree) or
+/// after (in-degree) another page.
+#[derive(Debug, Default, PartialEq)]
+struct RuleStats {
+    /// Pages mentioned in the rules.
+    page_count: usize,
+    /// Total amount of rules.
+    rule_count: usize,
+    /// Most pages that must come after a single page.
+    max_out_degree: usize,
+    /// Most pages that must come before a single page.
+    max_in_degree: usize,
+    /// Average amount of pages that must come after a page.
+    average_out_degree: f64,
+}
+
+impl fmt::Display for RuleStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Pages:              {:>6}", self.page_count)?;
+        writeln!(f, "Rules:              {:>6}", self.rule_count)?;
+        writeln!(f, "Max out-degree:     {:>6}", self.max_out_degree)?;
+        writeln!(f, "Max in-degree:      {:>6}", self.max_in_degree)?;
+        write!(f, "Average out-degree: {:>6.2}", self.average_out_degree)
+    }
+}
+
+/// State of a run of Tarjan's strongly connected components algorithm.
+struct Tarjan<'a> {
+    /// Rules to visit.
+    rules: &'a PageRules,
+    /// Index to assign to the next page visited.
+    next_index: usize,
+    /// Order in which pages were visited.
+    indices: HashMap<u32, usize>,
+    /// Lowest index reachable from each page.
+    low_links: HashMap<u32, usize>,
+    /// Pages visited but not yet assigned to a component.
+    stack: Vec<u32>,
+    /// Pages currently in `stack`.
+    on_stack: HashSet<u32>,
+    /// Components found so far.
+    components: Vec<Vec<u32>>,
+}
+
+impl Tarjan<'_> {
+    /// Visits page `x` and the pages reachable from it.
+    fn visit(&mut self, x: u32) {
+        self.indices.insert(x, self.next_index);
+        self.low_links.insert(x, self.next_index);
+        self.next_index += 1;
+        self.stack.push(x);
+        self.on_stack.insert(x);
+
+        for &y in self.rules.get(x).into_iter().flatten() {
+            if !self.indices.contains_key(&y) {
+                self.visit(y);
+                let low = self.low_links[&x].min(self.low_links[&y]);
+                self.low_links.insert(x, low);
+            } else if self.on_stack.contains(&y) {
+                let low = self.low_links[&x].min(self.indices[&y]);
+                self.low_links.insert(x, low);
+            }
+        }
+
+        if self.low_links[&x] == self.indices[&x] {
+            // `x` is the root of a component: everything above it in the stack belongs to it.
+            let mut component = vec![];
+            while let Some(y) = self.stack.pop() {
+                self.on_stack.remove(&y);
+                component.push(y);
+                if y == x {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
 }
 
 /// A manual page update.
-#[derive(Debug)]
+///
+/// Updates are stored in configuration files as arrays of pages.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 struct Update(Vec<u32>);
 
+/// The rules and updates making up a puzzle input.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+struct PuzzleInput {
+    rules: PageRules,
+    updates: Vec<Update>,
+}
+
+#[cfg(feature = "serde")]
+impl PuzzleInput {
+    /// Serializes `self` as JSON, in the form `{"rules":{"rules":[[x,y],...]},"updates":[...]}`.
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serializing a puzzle input should succeed")
+    }
+
+    /// Parses a puzzle input serialized as JSON by `to_json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid JSON or does not describe a puzzle input.
+    fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
 impl FromStr for Update {
     type Err = ParseIntError;
 
@@ -72,26 +719,166 @@ impl Update {
         });
     }
 
+    /// Sorts this update by the rank of each page in `order`.
+    ///
+    /// `order` maps each page to its position in a topological order of the rules, as returned by
+    /// `PageRules::topological_order`. Pages missing from `order` are moved to the end, keeping
+    /// their relative order.
+    fn sort_topological(&mut self, order: &HashMap<u32, usize>) {
+        self.0
+            .sort_by_cached_key(|p| order.get(p).copied().unwrap_or(usize::MAX));
+    }
+
+    /// Sorts this update according to `rules`, preserving the order of unrelated pages.
+    ///
+    /// Pages not mentioned in `rules` keep their original position, and thus their original
+    /// relative order. Pages with no rule between them keep their relative order too, unless
+    /// other rules force them apart.
+    ///
+    /// Pages caught in circular rules have no valid position, and neither have the pages that
+    /// must come after them, directly or not. All of these are left unsorted, placed after the
+    /// others in their original relative order, and may break rules even outside the cycle.
+    fn stable_sort(&mut self, rules: &PageRules) {
+        let pages = rules.pages();
+        let (slots, known): (Vec<usize>, Vec<u32>) = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| pages.contains(p))
+            .unzip();
+
+        // `rules.compare` is not a total order, so pages are ranked with Kahn's algorithm instead,
+        // always picking the earliest page that no remaining page must precede.
+        let n = known.len();
+        let before = |i: usize, j: usize| {
+            i != j && rules.get(known[i]).is_some_and(|ys| ys.contains(&known[j]))
+        };
+        let mut incoming: Vec<usize> = (0..n)
+            .map(|j| (0..n).filter(|&i| before(i, j)).count())
+            .collect();
+        let mut ready: BTreeSet<usize> = (0..n).filter(|&j| incoming[j] == 0).collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(i) = ready.pop_first() {
+            order.push(i);
+            for j in (0..n).filter(|&j| before(i, j)) {
+                incoming[j] -= 1;
+                if incoming[j] == 0 {
+                    ready.insert(j);
+                }
+            }
+        }
+        if order.len() < n {
+            let placed: HashSet<usize> = order.iter().copied().collect();
+            order.extend((0..n).filter(|i| !placed.contains(i)));
+        }
+
+        for (slot, i) in slots.into_iter().zip(order) {
+            self.0[slot] = known[i];
+        }
+    }
+
     /// Checks if the pages in this update are sorted according to `rules`.
     fn is_sorted(&self, rules: &PageRules) -> bool {
-        self.0.is_sorted_by(|x, y| match rules.get(*y) {
-            Some(xs) => !xs.contains(x),
-            None => true,
+        self.first_violation(rules).is_none()
+    }
+
+    /// Returns the indices `(i, j)` of the first pair of pages, with `i < j`, where `rules` say
+    /// the page at `j` must come before the one at `i`.
+    ///
+    /// If the pages are sorted, `None` is returned.
+    fn first_violation(&self, rules: &PageRules) -> Option<(usize, usize)> {
+        self.violations(rules).next()
+    }
+
+    /// Returns the indices `(i, j)` of all the pairs of pages, with `i < j`, where `rules` say the
+    /// page at `j` must come before the one at `i`.
+    fn all_violations(&self, rules: &PageRules) -> Vec<(usize, usize)> {
+        self.violations(rules).collect()
+    }
+
+    /// Returns an iterator over the misordered pairs of pages, in order of their indices.
+    fn violations<'a>(&'a self, rules: &'a PageRules) -> impl Iterator<Item = (usize, usize)> + 'a {
+        (0..self.0.len()).flat_map(move |i| {
+            (i + 1..self.0.len())
+                .filter(move |&j| {
+                    rules
+                        .get(self.0[j])
+                        .is_some_and(|xs| xs.contains(&self.0[i]))
+                })
+                .map(move |j| (i, j))
         })
     }
 
-    /// Returns the page number at `self`'s middle.
-    fn middle_page(&self) -> u32 {
-        self.0[self.0.len() / 2]
+    /// Returns the page number at `self`'s middle, or `None` if `self` is empty.
+    fn middle_page(&self) -> Option<u32> {
+        if self.0.is_empty() {
+            return None;
+        }
+        Some(self.0[self.0.len() / 2])
+    }
+
+    /// Checks if `page` appears in `self`.
+    fn contains(&self, page: u32) -> bool {
+        self.0.contains(&page)
+    }
+
+    /// Returns the amount of times `page` appears in `self`.
+    fn count_page(&self, page: u32) -> usize {
+        self.0.iter().filter(|&&p| p == page).count()
+    }
+
+    /// Returns the set of pages in `self`, ignoring duplicates.
+    fn distinct_pages(&self) -> HashSet<u32> {
+        self.0.iter().copied().collect()
     }
 }
 
-/// Sums the middle pages of `updates`.
-fn sum_middle_pages<'a, I>(updates: I) -> u32
+/// Possible errors when doing arithmetic on pages.
+#[derive(Debug, PartialEq)]
+enum ArithmeticError {
+    /// The result does not fit in the accumulator.
+    Overflow,
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+impl Error for ArithmeticError {}
+
+/// Sums the middle pages of `updates`, skipping empty updates.
+fn sum_middle_pages<'a, I>(updates: I) -> u64
 where
     I: IntoIterator<Item = &'a Update>,
 {
-    updates.into_iter().map(Update::middle_page).sum()
+    updates
+        .into_iter()
+        .filter_map(Update::middle_page)
+        .map(u64::from)
+        .sum()
+}
+
+/// Sums the middle pages of `updates`, skipping empty updates and checking for overflow.
+///
+/// # Errors
+///
+/// Returns an error if the sum does not fit in a `u64`.
+fn sum_middle_pages_checked<'a, I>(updates: I) -> Result<u64, ArithmeticError>
+where
+    I: IntoIterator<Item = &'a Update>,
+{
+    updates
+        .into_iter()
+        .filter_map(Update::middle_page)
+        .try_fold(0u64, |total, p| {
+            total
+                .checked_add(u64::from(p))
+                .ok_or(ArithmeticError::Overflow)
+        })
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -109,6 +896,11 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    let uncovered = rules.validate_completeness(&updates);
+    if !uncovered.is_empty() {
+        eprintln!("Warning: pages without rules: {uncovered:?}");
+    }
+
     let sorted = updates.iter().filter(|u| u.is_sorted(&rules));
     println!("Sum of middle pages: {}", sum_middle_pages(sorted));
 
@@ -117,8 +909,18 @@ fn main() -> Result<(), Box<dyn Error>> {
             .into_iter()
             .filter(|u| !u.is_sorted(&rules))
             .collect();
-        for u in &mut unsorted {
-            u.sort(&rules);
+        // Rules for the real input may be circular, in which case there is no global order and
+        // each update is sorted with the rules directly.
+        if let Some(order) = rules.topological_order() {
+            let order: HashMap<u32, usize> =
+                order.into_iter().enumerate().map(|(i, p)| (p, i)).collect();
+            for u in &mut unsorted {
+                u.sort_topological(&order);
+            }
+        } else {
+            for u in &mut unsorted {
+                u.sort(&rules);
+            }
         }
         unsorted
     };
@@ -162,6 +964,364 @@ mod tests {
             .unwrap()
     }
 
+    #[test]
+    fn rules_are_built_from_pairs() {
+        #[rustfmt::skip]
+        let pairs = [
+            (47, 53), (97, 13), (97, 61), (97, 47), (75, 29), (61, 13), (75, 53), (29, 13),
+            (97, 29), (53, 29), (61, 53), (97, 53), (61, 29), (47, 13), (75, 47), (97, 75),
+            (47, 61), (75, 61), (47, 29), (75, 13), (53, 13),
+        ];
+        let rules = PageRules::from_pairs(pairs);
+
+        assert_eq!(rules, get_test_rules());
+        assert_eq!(rules.rule_count(), get_test_rules().rule_count());
+        assert_eq!(pairs.into_iter().collect::<PageRules>(), rules);
+    }
+
+    #[test]
+    fn rules_report_their_contents() {
+        let rules = get_test_rules();
+
+        assert_eq!(rules.rule_count(), 21);
+        assert_eq!(rules.pages(), HashSet::from([13, 29, 47, 53, 61, 75, 97]));
+        assert_eq!(
+            rules.sources().collect::<HashSet<_>>(),
+            HashSet::from([29, 47, 53, 61, 75, 97])
+        );
+    }
+
+    #[test]
+    fn rules_are_displayed_as_adjacency_list() {
+        let rules = PageRules::from_pairs([(97, 13), (47, 61), (97, 61), (47, 53), (97, 47)]);
+        assert_eq!(rules.to_string(), "47|53,61\n97|13,47,61");
+        assert_eq!(format!("{rules:?}"), rules.to_string());
+
+        let rules = get_test_rules();
+        let s = rules.to_string();
+        let xs: Vec<u32> = s
+            .lines()
+            .map(|l| l.split_once('|').unwrap().0.parse().unwrap())
+            .collect();
+        assert!(xs.is_sorted());
+        for l in s.lines() {
+            let ys: Vec<u32> = l
+                .split_once('|')
+                .unwrap()
+                .1
+                .split(',')
+                .map(|y| y.parse().unwrap())
+                .collect();
+            assert!(ys.is_sorted());
+        }
+
+        assert_eq!(PageRules::from_adjacency_format(&s).unwrap(), rules);
+        assert!(PageRules::from_adjacency_format("47|").is_err());
+        assert!(PageRules::from_adjacency_format("47").is_err());
+        assert!(PageRules::from_adjacency_format("x|1").is_err());
+    }
+
+    #[test]
+    fn circular_rules_are_rejected_on_insert() {
+        let mut rules = PageRules::new();
+        assert_eq!(rules.insert_checked("47|53"), Ok(()));
+        assert_eq!(
+            rules.insert_checked("53|47"),
+            Err(InsertError::WouldCreateCycle {
+                new_rule: (53, 47),
+                existing_path: vec![47, 53],
+            })
+        );
+
+        assert_eq!(rules.insert_checked("53|29"), Ok(()));
+        assert_eq!(rules.insert_checked("47|29"), Ok(()));
+        assert_eq!(rules.insert_checked("29|13"), Ok(()));
+        let Err(InsertError::WouldCreateCycle { existing_path, .. }) =
+            rules.insert_checked("13|53")
+        else {
+            panic!("inserting 13|53 should create a cycle");
+        };
+        assert_eq!(existing_path.first(), Some(&53));
+        assert_eq!(existing_path.last(), Some(&13));
+        assert!(existing_path
+            .windows(2)
+            .all(|w| rules.get(w[0]).unwrap().contains(&w[1])));
+        assert!(rules.insert_checked("13|13").is_err());
+
+        assert!(!rules.has_cycle());
+        assert_eq!(
+            rules,
+            PageRules::from_pairs([(47, 53), (53, 29), (47, 29), (29, 13)])
+        );
+
+        assert!(matches!(
+            rules.insert_checked("13|x"),
+            Err(InsertError::ParseError(_))
+        ));
+        assert!(matches!(
+            rules.insert_checked("13"),
+            Err(InsertError::ParseError(_))
+        ));
+
+        let mut rules = PageRules::new();
+        for pair in get_test_rules().sorted_pairs() {
+            let rule = format!("{}|{}", pair.0, pair.1);
+            assert_eq!(rules.insert_checked(&rule), Ok(()));
+        }
+        assert_eq!(rules, get_test_rules());
+    }
+
+    #[test]
+    fn rules_compare_regardless_of_order() {
+        let a = PageRules::from_pairs([(1, 2), (1, 3), (2, 3)]);
+        let b = PageRules::from_pairs([(2, 3), (1, 3), (1, 2)]);
+        assert_eq!(a, b);
+        assert_ne!(a, PageRules::from_pairs([(1, 2), (1, 3)]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rules_round_trip_through_json() {
+        let rules = get_test_rules();
+        assert_eq!(PageRules::from_json(&rules.to_json()).unwrap(), rules);
+
+        let empty = PageRules::new();
+        assert_eq!(empty.to_json(), r#"{"rules":[]}"#);
+        assert_eq!(PageRules::from_json(&empty.to_json()).unwrap(), empty);
+
+        let rules = PageRules::from_json(r#"{"rules": [[47, 53], [97, 13]]}"#).unwrap();
+        assert_eq!(rules, PageRules::from_pairs([(47, 53), (97, 13)]));
+        assert!(PageRules::from_json(r#"{"rules": [[47]]}"#).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rules_round_trip_through_toml() {
+        let rules = get_test_rules();
+        assert_eq!(PageRules::from_toml(&rules.to_toml()).unwrap(), rules);
+
+        let empty = PageRules::new();
+        assert_eq!(PageRules::from_toml(&empty.to_toml()).unwrap(), empty);
+
+        let toml = "[[rules]]\nfrom = 47\nto = 53\n\n[[rules]]\nfrom = 97\nto = 13\n";
+        let rules = PageRules::from_toml(toml).unwrap();
+        assert_eq!(rules, PageRules::from_pairs([(47, 53), (97, 13)]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn puzzle_input_round_trips_through_json() {
+        let update = Update(vec![75, 47, 61, 53, 29]);
+        assert_eq!(serde_json::to_string(&update).unwrap(), "[75,47,61,53,29]");
+        assert_eq!(
+            serde_json::from_str::<Update>("[75, 47, 61, 53, 29]").unwrap(),
+            update
+        );
+
+        let input = PuzzleInput {
+            rules: get_test_rules(),
+            updates: get_test_updates(),
+        };
+        let json = input.to_json();
+        assert!(json.contains(r#""updates":[[75,47,61,53,29],"#));
+        assert_eq!(PuzzleInput::from_json(&json).unwrap(), input);
+        assert!(PuzzleInput::from_json(r#"{"rules":{"rules":[]}}"#).is_err());
+    }
+
+    #[test]
+    fn rules_are_removed() {
+        let mut rules = get_test_rules();
+
+        assert!(rules.remove_rule(47, 53));
+        assert_eq!(rules.rule_count(), 20);
+        assert!(!rules.remove_rule(47, 53));
+        assert!(!rules.remove_rule(13, 97));
+        assert_eq!(rules.rule_count(), 20);
+
+        rules.remove_page(97);
+        assert!(!rules.pages().contains(&97));
+        assert_eq!(rules.rule_count(), 14);
+
+        rules.remove_page(13);
+        assert_eq!(rules.pages(), HashSet::from([29, 47, 53, 61, 75]));
+    }
+
+    #[test]
+    fn inverted_rules_reverse_sorting() {
+        let rules = get_test_rules();
+        let inverted = rules.invert();
+        assert_eq!(inverted.rule_count(), rules.rule_count());
+        assert_eq!(inverted.invert(), rules);
+        assert!(!inverted.has_cycle());
+
+        for mut u in get_test_updates() {
+            u.sort(&rules);
+            let sorted = u.0.clone();
+
+            u.sort(&inverted);
+            assert!(u.is_sorted(&inverted));
+            assert_eq!(u.0, sorted.iter().rev().copied().collect::<Vec<_>>());
+
+            u.sort(&rules);
+            assert_eq!(u.0, sorted);
+        }
+    }
+
+    #[test]
+    fn rules_are_retained_by_predicate() {
+        let keep = |x: u32, y: u32| x < y;
+        let mut rules = get_test_rules();
+        rules.retain_rules(keep);
+
+        let expected = PageRules::from_pairs(
+            get_test_rules()
+                .0
+                .iter()
+                .flat_map(|(&x, ys)| ys.iter().map(move |&y| (x, y)))
+                .filter(|&(x, y)| keep(x, y)),
+        );
+        assert_eq!(rules, expected);
+        assert!(rules.rule_count() < get_test_rules().rule_count());
+    }
+
+    #[test]
+    fn rules_report_summary_stats() {
+        let stats = get_test_rules().summary_stats();
+        assert_eq!(
+            stats,
+            RuleStats {
+                page_count: 7,
+                rule_count: 21,
+                max_out_degree: 6,
+                max_in_degree: 6,
+                average_out_degree: 3.0,
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "Pages:                   7\n\
+             Rules:                  21\n\
+             Max out-degree:          6\n\
+             Max in-degree:           6\n\
+             Average out-degree:   3.00"
+        );
+
+        let mut rules = PageRules::new();
+        rules.insert("1|2").unwrap();
+        rules.insert("1|3").unwrap();
+        let stats = rules.summary_stats();
+        assert_eq!((stats.max_out_degree, stats.max_in_degree), (2, 1));
+        assert!((stats.average_out_degree - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        assert_eq!(PageRules::new().summary_stats(), RuleStats::default());
+    }
+
+    #[test]
+    fn rules_are_ordered_topologically() {
+        let rules = get_test_rules();
+
+        assert_eq!(
+            rules.topological_order(),
+            Some(vec![97, 75, 47, 61, 53, 29, 13])
+        );
+
+        let mut rules = PageRules::new();
+        for r in ["1|2", "2|3", "3|1"] {
+            rules.insert(r).unwrap();
+        }
+        assert!(rules.topological_order().is_none());
+    }
+
+    #[test]
+    fn acyclic_rules_have_singleton_components() {
+        let rules = get_test_rules();
+        let sccs = rules.strongly_connected_components();
+
+        assert_eq!(
+            sccs,
+            vec![
+                vec![13],
+                vec![29],
+                vec![53],
+                vec![61],
+                vec![47],
+                vec![75],
+                vec![97]
+            ]
+        );
+        assert!(!rules.has_cycle());
+    }
+
+    #[test]
+    fn cyclic_rules_are_grouped_in_components() {
+        let mut rules = PageRules::new();
+        for r in ["1|2", "2|3", "3|1", "3|4", "4|5", "5|5"] {
+            rules.insert(r).unwrap();
+        }
+        let mut sccs = rules.strongly_connected_components();
+        for scc in &mut sccs {
+            scc.sort_unstable();
+        }
+
+        assert_eq!(sccs, vec![vec![5], vec![4], vec![1, 2, 3]]);
+        assert!(rules.has_cycle());
+
+        let mut rules = PageRules::new();
+        rules.insert("5|5").unwrap();
+        assert!(rules.has_cycle());
+    }
+
+    #[test]
+    fn rules_report_chain_depths() {
+        let rules = get_test_rules();
+
+        assert_eq!(rules.depth(97), Ok(6));
+        assert_eq!(rules.depth(47), Ok(4));
+        assert_eq!(rules.depth(13), Ok(0));
+        assert_eq!(rules.depth(1), Ok(0));
+
+        let first = rules.topological_order().unwrap()[0];
+        assert_eq!(rules.max_depth(), rules.depth(first));
+
+        let mut rules = PageRules::new();
+        for r in ["1|2", "2|3", "3|1"] {
+            rules.insert(r).unwrap();
+        }
+        assert_eq!(rules.depth(1), Err(CycleError));
+        assert_eq!(rules.max_depth(), Err(CycleError));
+    }
+
+    #[test]
+    fn total_order_is_detected() {
+        let mut rules = PageRules::new();
+        for r in ["47|53", "97|13", "97|61", "53|13"] {
+            rules.insert(r).unwrap();
+        }
+        assert!(!rules.is_total_order());
+
+        // Fill in the gaps, following the order the existing rules already imply.
+        while let Some((x, y)) = rules.missing_pairs().first().copied() {
+            let order = rules.topological_order().unwrap();
+            let (x, y) = if order.iter().position(|&p| p == x) < order.iter().position(|&p| p == y)
+            {
+                (x, y)
+            } else {
+                (y, x)
+            };
+            rules.insert(&format!("{x}|{y}")).unwrap();
+        }
+        assert!(rules.is_total_order());
+
+        let order = rules.topological_order().unwrap();
+        assert!(order
+            .windows(2)
+            .all(|w| rules.compare(w[0], w[1]) == Ordering::Less));
+
+        // The test rules happen to cover every pair of their pages.
+        assert!(get_test_rules().is_total_order());
+        assert!(get_test_rules().missing_pairs().is_empty());
+    }
+
     #[test]
     fn valid_updates_pass_verification() {
         let rules = get_test_rules();
@@ -175,6 +1335,44 @@ mod tests {
         assert!(!updates[5].is_sorted(&rules));
     }
 
+    #[test]
+    fn rules_report_uncovered_pages() {
+        let rules = get_test_rules();
+        let mut updates = get_test_updates();
+        assert!(rules.validate_completeness(&updates).is_empty());
+        for u in &updates {
+            assert!(rules.are_all_pages_covered(&u.distinct_pages()));
+        }
+
+        // 42 is in no rule, so nothing keeps it from coming before 97.
+        let update = Update(vec![42, 97, 13]);
+        assert!(!rules.are_all_pages_covered(&update.distinct_pages()));
+        assert!(update.is_sorted(&rules));
+        updates.push(update);
+        assert_eq!(rules.validate_completeness(&updates), [42]);
+    }
+
+    #[test]
+    fn updates_report_misordered_pages() {
+        let rules = get_test_rules();
+        let updates = get_test_updates();
+
+        for u in &updates[..3] {
+            assert_eq!(u.first_violation(&rules), None);
+            assert!(u.all_violations(&rules).is_empty());
+        }
+
+        assert_eq!(updates[3].first_violation(&rules), Some((0, 1)));
+        assert_eq!(updates[3].all_violations(&rules), [(0, 1)]);
+        assert_eq!(updates[4].first_violation(&rules), Some((1, 2)));
+        assert_eq!(updates[4].all_violations(&rules), [(1, 2)]);
+        assert_eq!(updates[5].first_violation(&rules), Some((1, 2)));
+        assert_eq!(
+            updates[5].all_violations(&rules),
+            [(1, 2), (1, 3), (1, 4), (3, 4)]
+        );
+    }
+
     #[test]
     fn valid_updates_evaluate_to_correct_value() {
         let rules = get_test_rules();
@@ -186,6 +1384,61 @@ mod tests {
         assert_eq!(sum_middle_pages(&updates), 143);
     }
 
+    fn rank_map(rules: &PageRules) -> HashMap<u32, usize> {
+        rules
+            .topological_order()
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (p, i))
+            .collect()
+    }
+
+    #[test]
+    fn topological_sort_matches_rule_sort() {
+        let rules = get_test_rules();
+        let order = rank_map(&rules);
+        for update in get_test_updates() {
+            let mut expected = update.clone();
+            expected.sort(&rules);
+            let mut actual = update;
+            actual.sort_topological(&order);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark"]
+    fn topological_sort_throughput() {
+        let pages = 100;
+        let rules: PageRules = (0..pages)
+            .flat_map(|x| (x + 1..pages).map(move |y| (x, y)))
+            .collect();
+        let updates: Vec<Update> = (0..1_000)
+            .map(|i| Update((0..pages).map(|p| (p * 37 + i) % pages).collect()))
+            .collect();
+
+        let (by_rules, elapsed) = aoc2024::time(|| {
+            let mut updates = updates.clone();
+            for u in &mut updates {
+                u.sort(&rules);
+            }
+            updates
+        });
+        println!("Sorted {} updates with rules in {elapsed:?}", updates.len());
+
+        let (by_rank, elapsed) = aoc2024::time(|| {
+            let order = rank_map(&rules);
+            let mut updates = updates.clone();
+            for u in &mut updates {
+                u.sort_topological(&order);
+            }
+            updates
+        });
+        println!("Sorted {} updates by rank in {elapsed:?}", updates.len());
+        assert_eq!(by_rules, by_rank);
+    }
+
     #[test]
     fn sorted_updates_evaluate_to_correct_value() {
         let rules = get_test_rules();
@@ -200,4 +1453,88 @@ mod tests {
 
         assert_eq!(sum_middle_pages(&updates), 123);
     }
+
+    #[test]
+    fn middle_pages_sum_does_not_overflow() {
+        let rules = get_test_rules();
+        let updates: Vec<Update> = get_test_updates()
+            .into_iter()
+            .filter(|u| u.is_sorted(&rules))
+            .collect();
+        assert_eq!(sum_middle_pages_checked(&updates), Ok(143));
+
+        let updates = vec![Update(vec![u32::MAX]), Update(vec![1, u32::MAX, 2])];
+        let expected = u64::from(u32::MAX) * 2;
+        assert!(u32::MAX.checked_add(u32::MAX).is_none());
+        assert_eq!(sum_middle_pages(&updates), expected);
+        assert_eq!(sum_middle_pages_checked(&updates), Ok(expected));
+    }
+
+    #[test]
+    fn updates_answer_membership_queries() {
+        let updates = get_test_updates();
+
+        assert!(updates[0].contains(47));
+        assert!(!updates[0].contains(13));
+
+        let u = Update::from_str("75,47,61,47,29").unwrap();
+        assert_eq!(u.count_page(47), 2);
+        assert_eq!(u.count_page(75), 1);
+        assert_eq!(u.count_page(13), 0);
+
+        for u in &updates {
+            let max = u.0.iter().copied().max().unwrap();
+            let expected: HashSet<u32> = (0..=max).filter(|&p| u.contains(p)).collect();
+            assert_eq!(u.distinct_pages(), expected);
+            assert_eq!(u.middle_page(), Some(u.0[u.0.len() / 2]));
+        }
+
+        assert_eq!(Update(vec![]).middle_page(), None);
+        assert_eq!(
+            sum_middle_pages(&[Update(vec![]), Update(vec![1, 2, 3])]),
+            2
+        );
+    }
+
+    #[test]
+    fn stable_sort_preserves_unruled_pages() {
+        let rules = get_test_rules();
+        let mut update = Update::from_str("98,47,99,75,61").unwrap();
+
+        update.stable_sort(&rules);
+        assert_eq!(update.0, [98, 75, 99, 47, 61]);
+        assert!(update.is_sorted(&rules));
+    }
+
+    #[test]
+    fn stable_sort_handles_partial_and_circular_rules() {
+        // 4 and 2, as well as 3 and 1, have no rule between them and keep their relative order.
+        let rules = PageRules::from_pairs([(1, 2), (3, 4)]);
+        let mut update = Update::from_str("4,2,3,1").unwrap();
+        update.stable_sort(&rules);
+        assert_eq!(update.0, [3, 4, 1, 2]);
+        assert!(update.is_sorted(&rules));
+
+        // 1, 2 and 3 form a cycle, and 4 comes after it: they are all left unsorted, so 4 stays
+        // before 3 even if rule 3|4 says otherwise. 6 and 7 are unaffected and sorted first.
+        let rules = PageRules::from_pairs([(1, 2), (2, 3), (3, 1), (3, 4), (6, 7)]);
+        let mut update = Update::from_str("4,2,5,3,1").unwrap();
+        update.stable_sort(&rules);
+        assert_eq!(update.0, [4, 2, 5, 3, 1]);
+
+        let mut update = Update::from_str("7,4,2,6,3,1").unwrap();
+        update.stable_sort(&rules);
+        assert_eq!(update.0, [6, 7, 4, 2, 3, 1]);
+    }
+
+    #[test]
+    fn stable_sort_matches_sort_for_ruled_pages() {
+        let rules = get_test_rules();
+
+        for (mut unstable, mut stable) in get_test_updates().into_iter().zip(get_test_updates()) {
+            unstable.sort(&rules);
+            stable.stable_sort(&rules);
+            assert_eq!(unstable.0, stable.0);
+        }
+    }
 }
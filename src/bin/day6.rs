@@ -1,11 +1,21 @@
 #![warn(clippy::pedantic)]
+#![allow(dead_code)]
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error;
-use std::fmt;
+use std::fmt::{self, Write};
 use std::fs;
-use std::path::PathBuf;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::num::ParseIntError;
+use std::ops::{Add, Index, IndexMut, Range};
+use std::path::{Path, PathBuf};
 use std::result;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+
+use bit_vec::BitVec;
+use rayon::prelude::*;
 
 type Result<T> = result::Result<T, Error>;
 
@@ -15,6 +25,7 @@ enum Error {
     InvalidTile,
     NoGuard,
     InfiniteLoop,
+    InvalidMap(MapValidationError),
 }
 
 impl fmt::Display for Error {
@@ -23,15 +34,122 @@ impl fmt::Display for Error {
             Self::InvalidTile => write!(f, "invalid tile"),
             Self::NoGuard => write!(f, "no guard in tiles"),
             Self::InfiniteLoop => write!(f, "infinite loop detected"),
+            Self::InvalidMap(e) => write!(f, "invalid map: {e}"),
         }
     }
 }
 
 impl error::Error for Error {}
 
+impl From<MapValidationError> for Error {
+    fn from(e: MapValidationError) -> Self {
+        Self::InvalidMap(e)
+    }
+}
+
+/// Possible errors when parsing a patrol history.
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// A line has fewer columns than expected.
+    MissingColumn,
+    /// A line has more columns than expected.
+    ExtraColumn,
+    /// A step is not numbered after the line it is on.
+    StepOutOfOrder { expected: usize, found: usize },
+    /// A position does not fit in the map.
+    IndexOutOfRange(usize),
+    /// An integer column is malformed.
+    MalformedInteger(ParseIntError),
+    /// The direction column is not one of `^`, `v`, `<` or `>`.
+    InvalidDirection,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingColumn => write!(f, "missing column"),
+            Self::ExtraColumn => write!(f, "extra column"),
+            Self::StepOutOfOrder { expected, found } => {
+                write!(f, "expected step {expected}, found {found}")
+            }
+            Self::IndexOutOfRange(i) => write!(f, "index out of range: {i}"),
+            Self::MalformedInteger(e) => write!(f, "malformed integer: {e}"),
+            Self::InvalidDirection => write!(f, "invalid direction"),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<ParseIntError> for ParseError {
+    fn from(e: ParseIntError) -> Self {
+        Self::MalformedInteger(e)
+    }
+}
+
+/// Possible errors when reading a map from a file.
+#[derive(Debug)]
+enum MapFromPathError {
+    /// The file cannot be read.
+    Io(io::Error),
+    /// The file does not contain a valid map.
+    Map(Error),
+}
+
+impl fmt::Display for MapFromPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read map: {e}"),
+            Self::Map(e) => write!(f, "invalid map: {e}"),
+        }
+    }
+}
+
+impl error::Error for MapFromPathError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Map(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for MapFromPathError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<Error> for MapFromPathError {
+    fn from(e: Error) -> Self {
+        Self::Map(e)
+    }
+}
+
+/// Possible errors when patrolling with a step limit.
+#[derive(Debug, PartialEq)]
+enum BoundedPatrolError {
+    /// The guard took the maximum amount of steps without exiting.
+    StepLimitExceeded(usize),
+    /// The guard walks in circles.
+    InfiniteLoop,
+}
+
+impl fmt::Display for BoundedPatrolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::StepLimitExceeded(n) => write!(f, "step limit of {n} exceeded"),
+            Self::InfiniteLoop => write!(f, "infinite loop detected"),
+        }
+    }
+}
+
+impl error::Error for BoundedPatrolError {}
+
 /// A single tile in a map.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 enum Tile {
+    #[default]
     Ignored,
     Occupied,
     Guard(Direction),
@@ -50,17 +168,48 @@ impl TryFrom<char> for Tile {
     }
 }
 
+impl From<Tile> for char {
+    fn from(t: Tile) -> Self {
+        match t {
+            Tile::Ignored => '.',
+            Tile::Occupied => '#',
+            Tile::Guard(d) => d.into(),
+        }
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", char::from(*self))
+    }
+}
+
+/// An action taken by a guard on patrol.
+#[derive(Clone, Debug, PartialEq)]
+enum GuardEvent {
+    /// The guard stepped onto the tile at the given position.
+    Stepped(usize),
+    /// The guard turned to face the given direction.
+    Turned(Direction),
+    /// The guard left the map from the given position.
+    Exited(usize),
+    /// The guard bumped again into the obstacle at the given position, from the same direction.
+    LoopDetected(usize),
+}
+
 /// A guard patrolling a map.
 #[derive(Clone, Debug, Default, PartialEq)]
 struct Guard {
     /// Direction the guard is walking in.
     direction: Direction,
-    /// Current position.
-    position: usize,
-    /// Visited tiles. The first one is the starting position.
-    visited: Vec<usize>,
+    /// Current position, as `(row, col)` coordinates.
+    position: (i32, i32),
+    /// Visited tiles and the direction they were left in. The first one is the starting position.
+    visited: Vec<(usize, Direction)>,
     /// Obstacles encountered and the direction they were approached in.
     obstacles: HashMap<usize, HashSet<Direction>>,
+    /// Whether the last patrol was aborted because of an infinite loop.
+    looped: bool,
 }
 
 impl Guard {
@@ -71,70 +220,588 @@ impl Guard {
             if let Tile::Guard(d) = t {
                 return Some(Guard {
                     direction: *d,
-                    position: i,
+                    position: map.coords_of(i),
                     visited: Vec::with_capacity(map.tiles.len() - obstacles),
                     obstacles: HashMap::with_capacity(obstacles),
+                    looped: false,
                 });
             }
         }
         None
     }
 
-    /// Patrols `map` until `self` exits the room from an edge.
+    /// Patrols `map` until `self` exits the room from an edge, returning the amount of steps
+    /// taken.
+    ///
+    /// Turning in place is not a step.
+    ///
+    /// # Errors
+    ///
+    /// If an infinite loop is detected, an error is returned. The steps taken until then can be
+    /// retrieved with `path_length`.
+    fn patrol(&mut self, map: &Map) -> Result<usize> {
+        self.patrol_with(map, |_| {})
+    }
+
+    /// Patrols `map` like `patrol`, sending a `GuardEvent` to `tx` for every action taken.
+    ///
+    /// `tx` is dropped once the patrol ends, so receivers see the channel close. Events are
+    /// dropped if the receiving end of the channel hangs up.
+    ///
+    /// # Errors
+    ///
+    /// If an infinite loop is detected, `GuardEvent::LoopDetected` is sent and an error is
+    /// returned.
+    fn patrol_async(&mut self, map: &Map, tx: Sender<GuardEvent>) -> Result<usize> {
+        self.patrol_with(map, move |e| {
+            let _ = tx.send(e);
+        })
+    }
+
+    /// Patrols `map` until `self` exits the room from an edge, reporting each action to `observe`
+    /// and returning the amount of steps taken.
     ///
     /// # Errors
     ///
     /// If an infinite loop is detected, an error is returned.
-    fn patrol(&mut self, map: &Map) -> Result<()> {
+    fn patrol_with<F>(&mut self, map: &Map, mut observe: F) -> Result<usize>
+    where
+        F: FnMut(GuardEvent),
+    {
+        let mut steps = 0;
         loop {
-            let offset = self.compute_offset(map);
-            let Some(next_pos) = self.position.checked_add_signed(offset) else {
-                break;
-            };
+            if self.can_exit_immediately(map) {
+                // There is no need to look past the border.
+                let here = self.flat_position(map);
+                self.visited.push((here, self.direction));
+                observe(GuardEvent::Exited(here));
+                return Ok(steps);
+            }
 
-            if self.is_out_of_bounds(next_pos, map) {
-                // The guard exits the room.
-                self.visited.push(self.position);
-                break;
+            let event = self.advance(map);
+            observe(event.clone());
+            match event {
+                GuardEvent::Exited(_) => return Ok(steps),
+                GuardEvent::LoopDetected(_) => return Err(Error::InfiniteLoop),
+                GuardEvent::Stepped(_) => steps += 1,
+                GuardEvent::Turned(_) => {}
             }
-            if map.tiles[next_pos] == Tile::Occupied {
-                // The guard bumps on an obstacle.
-                // Bail if an infinite loop is detected.
-                self.log_obstacle(next_pos, self.direction)?;
-                self.turn();
-                continue;
+        }
+    }
+
+    /// Patrols `map` like `patrol`, giving up after `max_steps` actions.
+    ///
+    /// Every action counts as a step, including turning and exiting.
+    ///
+    /// # Errors
+    ///
+    /// If the step limit is reached before exiting or an infinite loop is detected, an error is
+    /// returned.
+    fn patrol_bounded(
+        &mut self,
+        map: &Map,
+        max_steps: usize,
+    ) -> result::Result<(), BoundedPatrolError> {
+        let mut steps = 0;
+        loop {
+            if steps == max_steps {
+                return Err(BoundedPatrolError::StepLimitExceeded(steps));
+            }
+            steps += 1;
+
+            match self.advance(map) {
+                GuardEvent::Exited(_) => return Ok(()),
+                GuardEvent::LoopDetected(_) => return Err(BoundedPatrolError::InfiniteLoop),
+                GuardEvent::Stepped(_) | GuardEvent::Turned(_) => {}
             }
+        }
+    }
 
-            self.visited.push(self.position);
-            self.position = next_pos;
+    /// Returns a `GuardStepper` patrolling `map` one action at a time.
+    fn stepper<'a>(&'a mut self, map: &'a Map) -> GuardStepper<'a> {
+        GuardStepper {
+            guard: self,
+            map,
+            done: false,
         }
+    }
 
-        Ok(())
+    /// Patrols `map` like `patrol`, returning the visited tiles as a bit per tile of `map`
+    /// instead of recording them in `self.visited`.
+    ///
+    /// This trades the order of the visits for a fixed memory footprint of one bit per tile.
+    ///
+    /// # Errors
+    ///
+    /// If an infinite loop is detected, an error is returned.
+    fn patrol_compact(&mut self, map: &Map) -> Result<BitVec> {
+        let mut visited = BitVec::from_elem(map.tiles.len(), false);
+        loop {
+            let (event, left) = self.act(map);
+            if let Some((pos, _)) = left {
+                visited.set(pos, true);
+            }
+            match event {
+                GuardEvent::Exited(_) => return Ok(visited),
+                GuardEvent::LoopDetected(_) => return Err(Error::InfiniteLoop),
+                GuardEvent::Stepped(_) | GuardEvent::Turned(_) => {}
+            }
+        }
+    }
+
+    /// Patrols `map` like `patrol`, remembering the obstacles bumped into in a `BloomFilter`.
+    ///
+    /// A patrol bumps at most once into each side of each obstacle before looping, so the filter
+    /// is sized for that many items. A false positive is reported as a spurious infinite loop:
+    /// with a rate of `PATROL_FALSE_POSITIVE_RATE` divided by the amount of lookups, the chance
+    /// of one happening anywhere in the patrol stays below `PATROL_FALSE_POSITIVE_RATE`.
+    ///
+    /// # Errors
+    ///
+    /// If an infinite loop is detected, an error is returned.
+    fn patrol_bloom(&mut self, map: &Map) -> Result<()> {
+        const PATROL_FALSE_POSITIVE_RATE: f64 = 1e-6;
+
+        let capacity = map.count_obstacles() * 4;
+        #[allow(clippy::cast_precision_loss)]
+        let rate = PATROL_FALSE_POSITIVE_RATE / capacity.max(1) as f64;
+        let mut bumps = BloomFilter::with_rate(capacity, rate);
+        loop {
+            let (event, left) =
+                self.act_with(map, |_, pos, direction| bumps.insert(pos, direction));
+            if let Some(visit) = left {
+                self.visited.push(visit);
+            }
+            match event {
+                GuardEvent::Exited(_) => return Ok(()),
+                GuardEvent::LoopDetected(_) => return Err(Error::InfiniteLoop),
+                GuardEvent::Stepped(_) | GuardEvent::Turned(_) => {}
+            }
+        }
+    }
+
+    /// Takes a single action on `map`, either stepping, turning or exiting.
+    fn advance(&mut self, map: &Map) -> GuardEvent {
+        let (event, left) = self.act(map);
+        if let Some(visit) = left {
+            self.visited.push(visit);
+        }
+        event
+    }
+
+    /// Takes a single action on `map`, returning the tile left and its direction, if any.
+    ///
+    /// The tile left is not recorded in `self.visited`.
+    fn act(&mut self, map: &Map) -> (GuardEvent, Option<(usize, Direction)>) {
+        self.act_with(map, |g, pos, direction| {
+            g.log_obstacle(pos, direction).is_err()
+        })
+    }
+
+    /// Takes a single action on `map` like `act`, asking `bumped` whether an obstacle was already
+    /// approached from the same direction.
+    ///
+    /// `bumped` is expected to remember every obstacle it is asked about.
+    fn act_with<F>(&mut self, map: &Map, bumped: F) -> (GuardEvent, Option<(usize, Direction)>)
+    where
+        F: FnOnce(&mut Self, usize, Direction) -> bool,
+    {
+        let here = self.flat_position(map);
+        let (dr, dc) = self.compute_offset();
+        let next = (self.position.0 + dr, self.position.1 + dc);
+        if Self::is_out_of_bounds(next, map) {
+            // The guard exits the room. The tile left is recorded for every edge, the top one
+            // included, since it was walked on like any other.
+            let left = (here, self.direction);
+            return (GuardEvent::Exited(here), Some(left));
+        }
+
+        let next_pos = map
+            .index_of(next)
+            .expect("a position within bounds should have an index");
+        if map.tiles[next_pos] == Tile::Occupied {
+            // The guard bumps on an obstacle.
+            // Bail if an infinite loop is detected.
+            if bumped(self, next_pos, self.direction) {
+                self.looped = true;
+                return (GuardEvent::LoopDetected(next_pos), None);
+            }
+            self.turn();
+            return (GuardEvent::Turned(self.direction), None);
+        }
+
+        let left = (here, self.direction);
+        self.position = next;
+        (GuardEvent::Stepped(next_pos), Some(left))
+    }
+
+    /// Reverts the last step taken by `self` on `map`, returning the position stepped away from.
+    ///
+    /// Turns taken after the step are reverted too, and the obstacles bumped into while turning
+    /// are forgotten. If `self` has not moved yet, `None` is returned.
+    fn backtrack(&mut self, map: &Map) -> Option<usize> {
+        let (pos, direction) = self.visited.pop()?;
+        let unstepped = self.flat_position(map);
+
+        let mut d = direction;
+        while d != self.direction {
+            let (dr, dc) = d.delta();
+            if let Some(obstacle) = map.index_of((self.position.0 + dr, self.position.1 + dc)) {
+                if let Some(approaches) = self.obstacles.get_mut(&obstacle) {
+                    approaches.remove(&d);
+                    if approaches.is_empty() {
+                        self.obstacles.remove(&obstacle);
+                    }
+                }
+            }
+            d = d.turned();
+        }
+
+        self.position = map.coords_of(pos);
+        self.direction = direction;
+        self.looped = false;
+        Some(unstepped)
     }
 
     /// Returns the coordinates of unique tiles visited.
     fn unique_visits(&self) -> HashSet<usize> {
-        self.visited.iter().copied().collect::<HashSet<_>>()
+        self.visited
+            .iter()
+            .map(|&(pos, _)| pos)
+            .collect::<HashSet<_>>()
+    }
+
+    /// Returns a `map`-shaped grid of rows, where `true` marks the tiles visited by `self`.
+    fn visited_grid(&self, map: &Map) -> Vec<Vec<bool>> {
+        self.visited_mask(map)
+            .chunks(map.width)
+            .map(|row| row.iter().map(|&v| v == 1).collect())
+            .collect()
+    }
+
+    /// Returns one byte per `map` tile, set to 1 if the tile was visited by `self` and 0 otherwise.
+    fn visited_mask(&self, map: &Map) -> Vec<u8> {
+        let mut mask = vec![0; map.tiles.len()];
+        for &(pos, _) in &self.visited {
+            mask[pos] = 1;
+        }
+        mask
+    }
+
+    /// Returns the amount of steps taken by `self`, counting revisited tiles every time.
+    fn path_length(&self) -> usize {
+        self.visited.len()
+    }
+
+    /// Returns the `((min_row, min_col), (max_row, max_col))` coordinates of the smallest box
+    /// enclosing every tile visited by `self` on `map`, including the current one.
+    fn bounding_box(&self, map: &Map) -> ((usize, usize), (usize, usize)) {
+        let here = self.flat_position(map);
+        let positions = self.visited.iter().map(|&(pos, _)| pos).chain([here]);
+
+        let (mut min, mut max) = ((usize::MAX, usize::MAX), (0, 0));
+        for pos in positions {
+            let (row, col) = (pos / map.width, pos % map.width);
+            min = (min.0.min(row), min.1.min(col));
+            max = (max.0.max(row), max.1.max(col));
+        }
+        (min, max)
+    }
+
+    /// Returns the amount of tiles in `self`'s bounding box on `map`.
+    fn path_area(&self, map: &Map) -> usize {
+        let ((min_row, min_col), (max_row, max_col)) = self.bounding_box(map);
+        (max_row - min_row + 1) * (max_col - min_col + 1)
+    }
+
+    /// Returns the first tile `self` walked on twice, if the last patrol ended in a loop.
+    fn loop_entry_point(&self, map: &Map) -> Option<usize> {
+        self.loop_bounds(map)
+            .map(|(start, _)| self.visited[start].0)
+    }
+
+    /// Returns the amount of steps between the two visits to the loop's entry point, if the last
+    /// patrol ended in a loop.
+    fn loop_period(&self, map: &Map) -> Option<usize> {
+        self.loop_bounds(map).map(|(start, end)| end - start)
+    }
+
+    /// Returns the steps at which the first tile visited twice was walked on, if the last patrol
+    /// ended in a loop.
+    ///
+    /// The guard's current position counts as the last step.
+    fn loop_bounds(&self, map: &Map) -> Option<(usize, usize)> {
+        if !self.looped {
+            return None;
+        }
+
+        let mut seen = HashMap::with_capacity(self.visited.len());
+        let positions = self.visited.iter().map(|&(pos, _)| pos);
+        for (step, pos) in positions.chain([self.flat_position(map)]).enumerate() {
+            if let Some(&first) = seen.get(&pos) {
+                return Some((first, step));
+            }
+            seen.insert(pos, step);
+        }
+        None
+    }
+
+    /// Returns the obstacles bumped into by `self`, keyed by their `(row, col)` coordinates on
+    /// `map`.
+    fn obstacles_as_coordinates(&self, map: &Map) -> HashMap<(usize, usize), HashSet<Direction>> {
+        self.obstacles
+            .iter()
+            .map(|(&pos, directions)| ((pos / map.width, pos % map.width), directions.clone()))
+            .collect()
+    }
+
+    /// Describes the obstacles bumped into by `self`, one per line, sorted by position.
+    fn obstacles_summary(&self, map: &Map) -> String {
+        let mut obstacles: Vec<_> = self.obstacles_as_coordinates(map).into_iter().collect();
+        obstacles.sort_unstable_by_key(|&(coords, _)| coords);
+
+        let mut summary = String::new();
+        for ((row, col), directions) in obstacles {
+            let directions: Vec<_> = [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ]
+            .into_iter()
+            .filter(|d| directions.contains(d))
+            .collect();
+            writeln!(
+                summary,
+                "(row={row}, col={col}) approached from {directions:?}"
+            )
+            .expect("writing to a String should succeed");
+        }
+        summary
+    }
+
+    /// Serializes the tiles visited by `self` as CSV.
+    ///
+    /// Each line has the format `step,flat_index,row,col,direction`, where `direction` is the one
+    /// the guard left the tile in.
+    fn to_csv(&self, map: &Map) -> String {
+        let mut csv = String::new();
+        for (step, &(pos, direction)) in self.visited.iter().enumerate() {
+            writeln!(
+                csv,
+                "{step},{pos},{},{},{}",
+                pos / map.width,
+                pos % map.width,
+                char::from(direction)
+            )
+            .expect("writing to a String should succeed");
+        }
+        csv
+    }
+
+    /// Draws `map` as an SVG image of `cell_size`-wide tiles, tracing the path walked by `self`.
+    ///
+    /// Obstacles are dark and every other tile grey. The starting position is marked with a blue
+    /// circle, the path with a green polyline and the tiles where the path crosses itself with red
+    /// dots.
+    fn path_as_svg(&self, map: &Map, cell_size: u32) -> String {
+        let size = usize::try_from(cell_size).expect("cell size should fit in a usize");
+        let (width, height) = (map.width * size, map.height() * size);
+        let radius = (size / 4).max(1);
+        let center = |pos: usize| {
+            let (row, col) = (pos / map.width, pos % map.width);
+            (col * size + size / 2, row * size + size / 2)
+        };
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{width}" height="{height}">"#
+        )
+        .expect("writing to a String should succeed");
+        for (pos, &t) in map.tiles.iter().enumerate() {
+            let fill = if t == Tile::Occupied {
+                "#333333"
+            } else {
+                "#cccccc"
+            };
+            let (row, col) = (pos / map.width, pos % map.width);
+            writeln!(
+                svg,
+                r#"  <rect x="{}" y="{}" width="{size}" height="{size}" fill="{fill}"/>"#,
+                col * size,
+                row * size
+            )
+            .expect("writing to a String should succeed");
+        }
+
+        let points: Vec<String> = self
+            .visited
+            .iter()
+            .map(|&(pos, _)| {
+                let (x, y) = center(pos);
+                format!("{x},{y}")
+            })
+            .collect();
+        writeln!(
+            svg,
+            r#"  <polyline points="{}" fill="none" stroke="green"/>"#,
+            points.join(" ")
+        )
+        .expect("writing to a String should succeed");
+
+        let start = self
+            .visited
+            .first()
+            .map_or_else(|| self.flat_position(map), |&(pos, _)| pos);
+        let (x, y) = center(start);
+        writeln!(
+            svg,
+            r#"  <circle cx="{x}" cy="{y}" r="{radius}" fill="blue"/>"#
+        )
+        .expect("writing to a String should succeed");
+
+        // A tile left both vertically and horizontally is one where the path crosses itself.
+        let mut axes: HashMap<usize, (bool, bool)> = HashMap::new();
+        for &(pos, direction) in &self.visited {
+            let (vertical, horizontal) = axes.entry(pos).or_default();
+            if matches!(direction, Direction::Up | Direction::Down) {
+                *vertical = true;
+            } else {
+                *horizontal = true;
+            }
+        }
+        let mut crossings: Vec<_> = axes
+            .into_iter()
+            .filter_map(|(pos, (vertical, horizontal))| (vertical && horizontal).then_some(pos))
+            .collect();
+        crossings.sort_unstable();
+        for pos in crossings {
+            let (x, y) = center(pos);
+            writeln!(
+                svg,
+                r#"  <circle cx="{x}" cy="{y}" r="{radius}" fill="red"/>"#
+            )
+            .expect("writing to a String should succeed");
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Parses a patrol history serialized by `to_csv`.
+    ///
+    /// The guard is placed on the last visited tile. Since the obstacles bumped into cannot be
+    /// recovered, they are left empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a line is malformed, steps are not numbered in order from 0 or a
+    /// position does not fit in `map`.
+    fn from_csv(s: &str, map: &Map) -> result::Result<Self, ParseError> {
+        let mut guard = Guard::default();
+        for (i, line) in s.lines().filter(|l| !l.is_empty()).enumerate() {
+            let cols: Vec<&str> = line.split(',').collect();
+            match cols.len() {
+                n if n < 5 => return Err(ParseError::MissingColumn),
+                n if n > 5 => return Err(ParseError::ExtraColumn),
+                _ => {}
+            }
+
+            let step: usize = cols[0].parse()?;
+            if step != i {
+                return Err(ParseError::StepOutOfOrder {
+                    expected: i,
+                    found: step,
+                });
+            }
+
+            let pos: usize = cols[1].parse()?;
+            let (row, col): (usize, usize) = (cols[2].parse()?, cols[3].parse()?);
+            let index = row.checked_mul(map.width).and_then(|r| r.checked_add(col));
+            if pos >= map.tiles.len()
+                || row >= map.height()
+                || col >= map.width
+                || index != Some(pos)
+            {
+                return Err(ParseError::IndexOutOfRange(pos));
+            }
+
+            let mut chars = cols[4].chars();
+            guard.direction = match (chars.next(), chars.next()) {
+                (Some(c), None) => c.try_into().map_err(|_| ParseError::InvalidDirection)?,
+                _ => return Err(ParseError::InvalidDirection),
+            };
+            guard.position = map.coords_of(pos);
+            guard.visited.push((pos, guard.direction));
+        }
+
+        Ok(guard)
+    }
+
+    /// Saves the state of `self`, so that it can be restored with `restore`.
+    fn snapshot(&self) -> GuardSnapshot {
+        GuardSnapshot {
+            direction: self.direction,
+            position: self.position,
+            visited_len: self.visited.len(),
+            obstacles: self.obstacles.clone(),
+            looped: self.looped,
+        }
+    }
+
+    /// Restores the state of `self` saved in `snap`, reusing the memory already allocated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` backtracked past the visits recorded when `snap` was taken.
+    fn restore(&mut self, snap: &GuardSnapshot) {
+        assert!(
+            self.visited.len() >= snap.visited_len,
+            "guard should not backtrack past its snapshot"
+        );
+        self.direction = snap.direction;
+        self.position = snap.position;
+        self.visited.truncate(snap.visited_len);
+        self.obstacles.clone_from(&snap.obstacles);
+        self.looped = snap.looped;
     }
 
     /// Turns `self` clockwise by one step.
     fn turn(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Right,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-            Direction::Right => Direction::Down,
-        }
+        self.direction = self.direction + 1;
     }
 
-    /// Checks if `next_pos` is out of `map`'s bounds, either horizontally or vertically.
-    fn is_out_of_bounds(&self, next_pos: usize, map: &Map) -> bool {
+    /// Checks if `self` stands on `map`'s border facing outwards, so that it exits with its next
+    /// action.
+    fn can_exit_immediately(&self, map: &Map) -> bool {
+        let (row, col) = self.position;
+        let (rows, cols) = map.coords_range();
         match self.direction {
-            Direction::Up | Direction::Down => next_pos >= map.tiles.len(),
-            Direction::Left | Direction::Right => next_pos / map.width != self.position / map.width,
+            Direction::Up => row == rows.start,
+            Direction::Down => row == rows.end - 1,
+            Direction::Left => col == cols.start,
+            Direction::Right => col == cols.end - 1,
         }
     }
 
+    /// Returns the index of `self`'s position among `map`'s tiles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not within `map`'s bounds.
+    fn flat_position(&self, map: &Map) -> usize {
+        map.index_of(self.position)
+            .expect("guard should be within the map's bounds")
+    }
+
+    /// Checks if the `(row, col)` coordinates in `next` are out of `map`'s bounds, either
+    /// horizontally or vertically.
+    fn is_out_of_bounds(next: (i32, i32), map: &Map) -> bool {
+        let (rows, cols) = map.coords_range();
+        !rows.contains(&next.0) || !cols.contains(&next.1)
+    }
+
     /// Logs an obstacle's position and the direction it was approached in.
     ///
     /// # Errors
@@ -150,14 +817,100 @@ impl Guard {
     }
 
     /// Computes the offset to reach the next `map` tile in `self.direction`.
-    fn compute_offset(&self, map: &Map) -> isize {
-        #[allow(clippy::cast_possible_wrap)]
-        match self.direction {
-            Direction::Up => -(map.width as isize),
-            Direction::Down => map.width as isize,
-            Direction::Left => -1,
-            Direction::Right => 1,
+    fn compute_offset(&self) -> (i32, i32) {
+        self.direction.delta()
+    }
+}
+
+/// The state of a guard at some point of its patrol, to be restored later.
+#[derive(Clone, Debug, PartialEq)]
+struct GuardSnapshot {
+    direction: Direction,
+    position: (i32, i32),
+    /// Amount of visited tiles, since visits are only ever appended while patrolling.
+    visited_len: usize,
+    obstacles: HashMap<usize, HashSet<Direction>>,
+    looped: bool,
+}
+
+/// A guard patrolling a map one action at a time.
+///
+/// The stepper ends after the guard exits the map or an infinite loop is detected.
+struct GuardStepper<'a> {
+    /// The guard on patrol.
+    guard: &'a mut Guard,
+    /// The map being patrolled.
+    map: &'a Map,
+    /// Whether the patrol is over.
+    done: bool,
+}
+
+impl Iterator for GuardStepper<'_> {
+    type Item = GuardEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let event = self.guard.advance(self.map);
+        self.done = matches!(event, GuardEvent::Exited(_) | GuardEvent::LoopDetected(_));
+        Some(event)
+    }
+}
+
+/// A probabilistic set of obstacles and the directions they were approached from.
+///
+/// Obstacles that were never inserted may be reported as present, but inserted ones are never
+/// reported as absent.
+///
+/// For very low rates, the filter can grow larger than an exact bitset of every tile and
+/// direction, which needs four bits per tile.
+struct BloomFilter {
+    bits: BitVec,
+    /// Amount of bits set for each item.
+    hashes: u64,
+}
+
+impl BloomFilter {
+    /// Creates a new, empty `BloomFilter` holding up to `capacity` items, where each lookup is a
+    /// false positive with a probability of about `rate`.
+    ///
+    /// The filter uses `m = -n·ln(p) / ln²(2)` bits and `k = (m / n)·ln(2)` hash functions, with
+    /// `n` the capacity and `p` the rate.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_sign_loss)]
+    fn with_rate(capacity: usize, rate: f64) -> Self {
+        let n = capacity.max(1) as f64;
+        let m = (-n * rate.ln() / std::f64::consts::LN_2.powi(2))
+            .ceil()
+            .max(1.0);
+        let k = (m / n * std::f64::consts::LN_2).round().max(1.0);
+        Self {
+            bits: BitVec::from_elem(m as usize, false),
+            hashes: k as u64,
+        }
+    }
+
+    /// Inserts an obstacle at `pos` approached towards `direction`, returning whether it was
+    /// already present.
+    fn insert(&mut self, pos: usize, direction: Direction) -> bool {
+        // Derive every bit from two halves of a single hash, as in double hashing.
+        let mut hasher = DefaultHasher::new();
+        (pos, direction).hash(&mut hasher);
+        let hash = hasher.finish();
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+
+        let len = self.bits.len() as u64;
+        let mut present = true;
+        for i in 0..self.hashes {
+            let bit = usize::try_from(h1.wrapping_add(i.wrapping_mul(h2)) % len)
+                .expect("bit index should fit in the filter");
+            present &= self.bits[bit];
+            self.bits.set(bit, true);
         }
+        present
     }
 }
 
@@ -184,64 +937,747 @@ impl TryFrom<char> for Direction {
     }
 }
 
-/// A map of tiles, with a guard on patrol.
-#[derive(Debug)]
-struct Map {
-    /// The tiles that compose the map.
-    tiles: Vec<Tile>,
-    /// Width of a single row.
-    width: usize,
+impl From<Direction> for char {
+    fn from(d: Direction) -> Self {
+        match d {
+            Direction::Up => '^',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+            Direction::Right => '>',
+        }
+    }
 }
 
-impl Map {
-    /// Creates a new `Map` from a newline-separated string.
-    fn new(s: &str) -> Result<Self> {
-        let tiles: Vec<Vec<Tile>> = s
-            .split('\n')
-            .map(|s| s.chars().map(Tile::try_from).collect())
-            .collect::<result::Result<Vec<_>, _>>()?;
-        let width = tiles[0].len();
+impl Add<i32> for Direction {
+    type Output = Self;
 
-        let tiles: Vec<Tile> = tiles.into_iter().flatten().collect();
-        Ok(Self { tiles, width })
+    /// Rotates `self` clockwise by `rhs` steps of 90 degrees.
+    fn add(self, rhs: i32) -> Self {
+        let current = match self {
+            Direction::Up => 0,
+            Direction::Right => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+        };
+        let steps = usize::try_from(rhs.rem_euclid(4)).expect("remainder should be positive");
+        Self::CLOCKWISE[(current + steps) % 4]
     }
+}
 
-    /// Returns the amount of obstacles in `self`.
+impl Direction {
+    /// Every direction, in clockwise order from `Up`.
+    const CLOCKWISE: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    /// Returns the direction one clockwise step from `self`.
+    fn turned(self) -> Self {
+        self + 1
+    }
+
+    /// Returns the direction opposite to `self`.
+    fn opposite(self) -> Self {
+        self + 2
+    }
+
+    /// Returns the direction `n` clockwise steps from `self`, or counter-clockwise if negative.
+    fn rotate_n(self, n: i32) -> Self {
+        self + n
+    }
+
+    /// Returns the `(row, col)` deltas to reach the next tile in `self`'s direction.
+    fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// Amount of tiles of each kind in a map.
+#[derive(Debug, Default, PartialEq)]
+struct TileBreakdown {
+    /// Tiles that can be walked on.
+    ignored: usize,
+    /// Tiles with an obstacle.
+    occupied: usize,
+    /// Tiles with a guard.
+    guard: usize,
+}
+
+impl TileBreakdown {
+    /// Returns the total amount of tiles.
+    fn total(&self) -> usize {
+        self.ignored + self.occupied + self.guard
+    }
+}
+
+impl fmt::Display for TileBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Ignored:  {:>6}", self.ignored)?;
+        writeln!(f, "Occupied: {:>6}", self.occupied)?;
+        writeln!(f, "Guard:    {:>6}", self.guard)?;
+        write!(f, "Total:    {:>6}", self.total())
+    }
+}
+
+/// Possible errors when parsing a run-length encoded map.
+#[derive(Debug, PartialEq)]
+enum RleError {
+    /// A character does not represent a tile.
+    InvalidTile(char),
+    /// A run length is malformed.
+    InvalidCount(ParseIntError),
+    /// A run length is not followed by a tile.
+    DanglingCount,
+    /// The amount of tiles does not fill whole rows.
+    LengthMismatch { expected: usize, got: usize },
+    /// The tiles would exceed the largest map allowed.
+    TooManyTiles { limit: usize },
+}
+
+impl fmt::Display for RleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidTile(c) => write!(f, "invalid tile: {c}"),
+            Self::InvalidCount(e) => write!(f, "invalid count: {e}"),
+            Self::DanglingCount => write!(f, "count without tile"),
+            Self::LengthMismatch { expected, got } => {
+                write!(f, "expected {expected} tiles, got {got}")
+            }
+            Self::TooManyTiles { limit } => write!(f, "more than {limit} tiles"),
+        }
+    }
+}
+
+impl error::Error for RleError {}
+
+/// Possible errors when validating a map.
+#[derive(Debug, PartialEq)]
+enum MapValidationError {
+    /// There is no guard on the map.
+    NoGuard,
+    /// There is more than one guard on the map.
+    MultipleGuards(usize),
+    /// The tiles do not fill whole rows.
+    UnevenRows { tiles: usize, width: usize },
+    /// The guard is enclosed by obstacles on every side.
+    GuardTrapped { position: usize },
+    /// A row has a different length than the first one.
+    RaggedRow {
+        row: usize,
+        len: usize,
+        width: usize,
+    },
+}
+
+impl fmt::Display for MapValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoGuard => write!(f, "no guard in tiles"),
+            Self::MultipleGuards(n) => write!(f, "expected one guard, got {n}"),
+            Self::UnevenRows { tiles, width } => {
+                write!(f, "cannot split {tiles} tiles in rows of width {width}")
+            }
+            Self::GuardTrapped { position } => {
+                write!(f, "guard at {position} is enclosed by obstacles")
+            }
+            Self::RaggedRow { row, len, width } => {
+                write!(f, "row {row} has {len} tiles, expected {width}")
+            }
+        }
+    }
+}
+
+impl error::Error for MapValidationError {}
+
+/// The tiles of a map at some point, to be restored later.
+#[derive(Clone, Debug, PartialEq)]
+struct MapSnapshot(Vec<Tile>);
+
+/// A map of tiles, with a guard on patrol.
+#[derive(Clone, Debug, PartialEq)]
+struct Map {
+    /// The tiles that compose the map.
+    tiles: Vec<Tile>,
+    /// Width of a single row.
+    width: usize,
+}
+
+impl FromStr for Map {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, row) in self.tiles.chunks(self.width).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for t in row {
+                write!(f, "{t}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Index<(usize, usize)> for Map {
+    type Output = Tile;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        &self.tiles[self.checked_index(row, col)]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Map {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Self::Output {
+        let i = self.checked_index(row, col);
+        &mut self.tiles[i]
+    }
+}
+
+impl Map {
+    /// Largest amount of rows accepted by `from_rle`, so that huge run lengths cannot exhaust
+    /// memory.
+    const MAX_RLE_ROWS: usize = 1 << 16;
+
+    /// Returns the index of the tile at `(row, col)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(row, col)` is out of `self`'s bounds.
+    fn checked_index(&self, row: usize, col: usize) -> usize {
+        assert!(
+            row < self.height() && col < self.width,
+            "coordinates ({row}, {col}) out of bounds for {}x{} map",
+            self.height(),
+            self.width
+        );
+        row * self.width + col
+    }
+
+    /// Creates a new `Map` from a newline-separated string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a tile is invalid or the rows have different lengths.
+    fn new(s: &str) -> Result<Self> {
+        let rows: Vec<Vec<Tile>> = s
+            .lines()
+            .map(|s| s.chars().map(Tile::try_from).collect())
+            .collect::<result::Result<Vec<_>, _>>()?;
+        let width = Self::row_width(&rows)?;
+
+        let tiles: Vec<Tile> = rows.into_iter().flatten().collect();
+        Ok(Self { tiles, width })
+    }
+
+    /// Returns the length of the first of `rows`, checking that every other row is as long.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first row with a different length.
+    fn row_width(rows: &[Vec<Tile>]) -> result::Result<usize, MapValidationError> {
+        let width = rows.first().map_or(0, Vec::len);
+        if let Some((row, r)) = rows.iter().enumerate().find(|(_, r)| r.len() != width) {
+            return Err(MapValidationError::RaggedRow {
+                row,
+                len: r.len(),
+                width,
+            });
+        }
+        Ok(width)
+    }
+
+    /// Creates a new `Map` from `rows` of tiles, which must all be as wide as the first one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rows have different lengths or the map is not valid.
+    fn from_rows(rows: Vec<Vec<Tile>>) -> result::Result<Self, MapValidationError> {
+        let width = Self::row_width(&rows)?;
+        Self::from_flat(rows.into_iter().flatten().collect(), width)
+    }
+
+    /// Creates a new `Map` from `tiles`, split in rows of `width` tiles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tiles do not fill whole rows or the map is not valid.
+    fn from_flat(tiles: Vec<Tile>, width: usize) -> result::Result<Self, MapValidationError> {
+        let map = Self { tiles, width };
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Saves the tiles of `self`, so that they can be restored with `restore`.
+    fn snapshot(&self) -> MapSnapshot {
+        MapSnapshot(self.tiles.clone())
+    }
+
+    /// Restores the tiles of `self` saved in `snap`, reusing the memory already allocated when
+    /// possible.
+    fn restore(&mut self, snap: &MapSnapshot) {
+        self.tiles.clone_from(&snap.0);
+    }
+
+    /// Returns the rows of tiles in `self`.
+    fn rows(&self) -> Vec<Vec<Tile>> {
+        self.tiles
+            .chunks(self.width)
+            .map(<[Tile]>::to_vec)
+            .collect()
+    }
+
+    /// Creates a new `Map` from the newline-separated contents of the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not contain a valid map.
+    fn from_path(path: &Path) -> result::Result<Self, MapFromPathError> {
+        let data = fs::read_to_string(path)?;
+        Ok(Self::new(&data)?)
+    }
+
+    /// Creates a new `Map` from a run-length encoded string, split in rows of `width` tiles.
+    ///
+    /// Each tile may be preceded by the amount of times it repeats, so that `3.#` stands for
+    /// `...#`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is malformed, the tiles do not fill whole rows or they
+    /// would span more than `MAX_RLE_ROWS` rows.
+    fn from_rle(s: &str, width: usize) -> result::Result<Self, RleError> {
+        let limit = width.max(1).saturating_mul(Self::MAX_RLE_ROWS);
+        let mut tiles = vec![];
+        let mut count = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                count.push(c);
+                continue;
+            }
+
+            let tile = Tile::try_from(c).map_err(|_| RleError::InvalidTile(c))?;
+            let n = if count.is_empty() {
+                1
+            } else {
+                count.parse().map_err(RleError::InvalidCount)?
+            };
+            if n > limit - tiles.len() {
+                return Err(RleError::TooManyTiles { limit });
+            }
+            tiles.extend(std::iter::repeat_n(tile, n));
+            count.clear();
+        }
+        if !count.is_empty() {
+            return Err(RleError::DanglingCount);
+        }
+
+        if width == 0 || !tiles.len().is_multiple_of(width) {
+            return Err(RleError::LengthMismatch {
+                expected: tiles.len().next_multiple_of(width.max(1)),
+                got: tiles.len(),
+            });
+        }
+        Ok(Self { tiles, width })
+    }
+
+    /// Encodes `self` as a run-length encoded string, omitting run lengths of 1.
+    fn to_rle(&self) -> String {
+        let mut rle = String::new();
+        let mut tiles = self.tiles.iter().peekable();
+        while let Some(t) = tiles.next() {
+            let mut n = 1;
+            while tiles.next_if_eq(&t).is_some() {
+                n += 1;
+            }
+            if n > 1 {
+                rle.push_str(&n.to_string());
+            }
+            rle.push(char::from(*t));
+        }
+        rle
+    }
+
+    /// Checks if the tile at `pos` lies in the first or last row or column of `self`.
+    fn is_border_tile(&self, pos: usize) -> bool {
+        let (row, col) = (pos / self.width, pos % self.width);
+        row == 0 || row == self.height() - 1 || col == 0 || col == self.width - 1
+    }
+
+    /// Returns the positions of all the tiles on `self`'s border.
+    fn border_positions(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.tiles.len()).filter(|&pos| self.is_border_tile(pos))
+    }
+
+    /// Returns the `(row, col)` coordinates of the tile at `pos`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coordinate does not fit in an `i32`.
+    fn coords_of(&self, pos: usize) -> (i32, i32) {
+        let row = i32::try_from(pos / self.width).expect("row should fit in an i32");
+        let col = i32::try_from(pos % self.width).expect("column should fit in an i32");
+        (row, col)
+    }
+
+    /// Returns the index of the tile at the `(row, col)` coordinates in `coords`, if within
+    /// `self`'s bounds.
+    fn index_of(&self, (row, col): (i32, i32)) -> Option<usize> {
+        let (row, col) = (usize::try_from(row).ok()?, usize::try_from(col).ok()?);
+        (row < self.height() && col < self.width).then_some(row * self.width + col)
+    }
+
+    /// Returns the ranges of valid row and column coordinates in `self`.
+    fn coords_range(&self) -> (Range<i32>, Range<i32>) {
+        let rows = i32::try_from(self.height()).expect("height should fit in an i32");
+        let cols = i32::try_from(self.width).expect("width should fit in an i32");
+        (0..rows, 0..cols)
+    }
+
+    /// Returns the tile at the `(row, col)` coordinates in `position`, if within `self`'s bounds.
+    fn tile_at_position(&self, position: (i32, i32)) -> Option<Tile> {
+        self.index_of(position).map(|i| self.tiles[i])
+    }
+
+    /// Returns the amount of rows in `self`.
+    fn height(&self) -> usize {
+        self.tiles.len() / self.width
+    }
+
+    /// Counts the tiles of each kind in `self`.
+    fn tile_count(&self) -> TileBreakdown {
+        let mut breakdown = TileBreakdown::default();
+        for t in &self.tiles {
+            match t {
+                Tile::Ignored => breakdown.ignored += 1,
+                Tile::Occupied => breakdown.occupied += 1,
+                Tile::Guard(_) => breakdown.guard += 1,
+            }
+        }
+        breakdown
+    }
+
+    /// Returns the amount of guards in `self`.
+    fn guard_count(&self) -> usize {
+        self.tile_count().guard
+    }
+
+    /// Returns the amount of tiles in `self` equal to `tile`.
+    ///
+    /// Guards only match if they face the same direction.
+    fn count_tile(&self, tile: Tile) -> usize {
+        self.tiles.iter().filter(|&&t| t == tile).count()
+    }
+
+    /// Counts the tiles of each kind in `self`, keyed by a human-readable name.
+    fn tile_frequencies(&self) -> HashMap<&'static str, usize> {
+        let breakdown = self.tile_count();
+        HashMap::from([
+            ("ignored", breakdown.ignored),
+            ("occupied", breakdown.occupied),
+            ("guard", breakdown.guard),
+        ])
+    }
+
+    /// Checks that `self` has whole rows and exactly one guard, who can leave its tile.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first problem found.
+    fn validate(&self) -> result::Result<(), MapValidationError> {
+        if self.width == 0 || !self.tiles.len().is_multiple_of(self.width) {
+            return Err(MapValidationError::UnevenRows {
+                tiles: self.tiles.len(),
+                width: self.width,
+            });
+        }
+
+        match self.guard_count() {
+            0 => return Err(MapValidationError::NoGuard),
+            1 => {}
+            n => return Err(MapValidationError::MultipleGuards(n)),
+        }
+
+        let guard = Guard::find(self).expect("map should have a guard");
+        if self.is_trapped(guard.position) {
+            return Err(MapValidationError::GuardTrapped {
+                position: guard.flat_position(self),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks if every tile next to the `(row, col)` coordinates in `position` is an obstacle.
+    ///
+    /// Moving out of `self` counts as a way out.
+    fn is_trapped(&self, position: (i32, i32)) -> bool {
+        Direction::CLOCKWISE.into_iter().all(|d| {
+            let (dr, dc) = d.delta();
+            self.tile_at_position((position.0 + dr, position.1 + dc)) == Some(Tile::Occupied)
+        })
+    }
+
+    /// Returns the positions of all tiles reachable from `start` without crossing obstacles.
+    ///
+    /// Tiles are reached by moving up, down, left or right. The result is empty if `start` is out
+    /// of bounds or holds an obstacle.
+    fn flood_fill(&self, start: usize) -> HashSet<usize> {
+        let mut reached = HashSet::new();
+        if self.tiles.get(start).is_none_or(|&t| t == Tile::Occupied) {
+            return reached;
+        }
+
+        let mut queue = VecDeque::from([start]);
+        reached.insert(start);
+        while let Some(pos) = queue.pop_front() {
+            let (row, col) = self.coords_of(pos);
+            for d in Direction::CLOCKWISE {
+                let (dr, dc) = d.delta();
+                let Some(next) = self.index_of((row + dr, col + dc)) else {
+                    continue;
+                };
+                if self.tiles[next] != Tile::Occupied && reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Partitions the tiles of `self` without obstacles into regions connected to each other.
+    ///
+    /// Regions are ordered by their first tile.
+    fn connected_components(&self) -> Vec<HashSet<usize>> {
+        let mut components: Vec<HashSet<usize>> = vec![];
+        let mut seen = vec![false; self.tiles.len()];
+        for (pos, &t) in self.tiles.iter().enumerate() {
+            if t != Tile::Occupied && !seen[pos] {
+                let component = self.flood_fill(pos);
+                for &p in &component {
+                    seen[p] = true;
+                }
+                components.push(component);
+            }
+        }
+        components
+    }
+
+    /// Returns the amount of obstacles in `self`.
     fn count_obstacles(&self) -> usize {
         self.tiles.iter().filter(|&t| *t == Tile::Occupied).count()
     }
+
+    /// Writes `self` to `writer`, tracing the path walked by `guard`.
+    ///
+    /// Visited tiles are marked with `X`, while the guard's current position is marked with its
+    /// direction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails.
+    fn print_path<W: io::Write>(&self, guard: &Guard, writer: &mut W) -> io::Result<()> {
+        let visited = guard.unique_visits();
+        for row in self.path_rows(guard, &visited) {
+            let row: String = row.into_iter().map(|(c, _)| c).collect();
+            writeln!(writer, "{row}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes `self` to `writer` as an HTML table, tracing the path walked by `guard`.
+    ///
+    /// Visited tiles are coloured green, obstacles red and the guard blue.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails.
+    fn print_path_html<W: io::Write>(&self, guard: &Guard, writer: &mut W) -> io::Result<()> {
+        let visited = guard.unique_visits();
+        writeln!(writer, "<style>")?;
+        writeln!(writer, ".visited {{ background-color: green; }}")?;
+        writeln!(writer, ".occupied {{ background-color: red; }}")?;
+        writeln!(writer, ".guard {{ background-color: blue; }}")?;
+        writeln!(writer, "</style>")?;
+        writeln!(writer, "<table>")?;
+        for row in self.path_rows(guard, &visited) {
+            write!(writer, "<tr>")?;
+            for (c, class) in row {
+                match class {
+                    Some(class) => write!(writer, "<td class=\"{class}\">{c}</td>")?,
+                    None => write!(writer, "<td>{c}</td>")?,
+                }
+            }
+            writeln!(writer, "</tr>")?;
+        }
+        writeln!(writer, "</table>")
+    }
+
+    /// Returns the rows of `self` as they should be drawn by `print_path` and `print_path_html`.
+    ///
+    /// Each tile is paired with the CSS class it should be styled with, if any.
+    fn path_rows(
+        &self,
+        guard: &Guard,
+        visited: &HashSet<usize>,
+    ) -> Vec<Vec<(char, Option<&'static str>)>> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(pos, t)| {
+                if pos == guard.flat_position(self) {
+                    (char::from(guard.direction), Some("guard"))
+                } else if *t == Tile::Occupied {
+                    ('#', Some("occupied"))
+                } else if visited.contains(&pos) {
+                    ('X', Some("visited"))
+                } else {
+                    ('.', None)
+                }
+            })
+            .collect::<Vec<_>>()
+            .chunks(self.width)
+            .map(<[_]>::to_vec)
+            .collect()
+    }
+
+    /// Patrols `self` once for each starting direction, in clockwise order from `Up`, returning
+    /// the guard at the end of each patrol.
+    ///
+    /// The guard always starts from its own tile, whichever direction it faces on the map. If
+    /// there is no guard, every patrol fails with `Error::NoGuard`.
+    fn guard_traces(&self) -> Vec<result::Result<Guard, Error>> {
+        Direction::CLOCKWISE
+            .into_iter()
+            .map(|direction| {
+                let mut guard = Guard {
+                    direction,
+                    ..Guard::find(self).ok_or(Error::NoGuard)?
+                };
+                guard.patrol(self)?;
+                Ok(guard)
+            })
+            .collect()
+    }
+
+    /// Returns the starting direction in which the guard visits the most unique tiles before
+    /// exiting `self`.
+    ///
+    /// Ties are broken in favor of the earliest direction in clockwise order from `Up`. If the
+    /// guard is stuck in a loop in every direction, or there is no guard, `None` is returned.
+    fn max_coverage_direction(&self) -> Option<Direction> {
+        let mut best: Option<(Direction, usize)> = None;
+        for (direction, trace) in Direction::CLOCKWISE.into_iter().zip(self.guard_traces()) {
+            let Ok(guard) = trace else {
+                continue;
+            };
+            let coverage = guard.unique_visits().len();
+            if best.is_none_or(|(_, c)| coverage > c) {
+                best = Some((direction, coverage));
+            }
+        }
+        best.map(|(d, _)| d)
+    }
+
+    /// Finds the fewest obstacles that must be added to `self` to trap the guard in a loop.
+    ///
+    /// Configurations are searched by increasing amount of obstacles, up to `max_obstacles`.
+    /// Returns `usize::MAX` if no configuration within the bound traps the guard, or if there is
+    /// no guard at all.
+    fn minimum_obstacles_to_trap(&self, max_obstacles: usize) -> usize {
+        let Some(guard) = Guard::find(self) else {
+            return usize::MAX;
+        };
+
+        let mut map = self.clone();
+        (0..=max_obstacles)
+            .find(|&n| map.can_trap(&guard, n))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Checks if adding at most `n` obstacles to `self` traps `guard` in a loop.
+    ///
+    /// Only the tiles visited by the guard are tried, since obstacles placed elsewhere cannot
+    /// alter its path.
+    fn can_trap(&mut self, guard: &Guard, n: usize) -> bool {
+        let mut g = guard.clone();
+        match g.patrol(self) {
+            Err(Error::InfiniteLoop) => return true,
+            Err(_) => return false,
+            Ok(_) if n == 0 => return false,
+            Ok(_) => {}
+        }
+
+        for tile in g.unique_visits() {
+            if tile == guard.flat_position(self) {
+                continue;
+            }
+
+            self.tiles[tile] = Tile::Occupied;
+            let trapped = self.can_trap(guard, n - 1);
+            self.tiles[tile] = Tile::Ignored;
+            if trapped {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 fn count_loops<I>(tiles: I, map: &mut Map) -> Result<usize>
 where
     I: IntoIterator<Item = usize>,
 {
-    let base_guard = Guard::find(map).ok_or(Error::NoGuard)?;
+    let mut guard = Guard::find(map).ok_or(Error::NoGuard)?;
+    let start = guard.snapshot();
     let mut loops = 0;
     for tile in tiles {
-        if tile == base_guard.position {
+        if tile == guard.flat_position(map) {
             continue;
         }
 
-        let mut guard = base_guard.clone();
-        map.tiles[tile] = Tile::Occupied;
+        let coords = (tile / map.width, tile % map.width);
+        map[coords] = Tile::Occupied;
         if let Err(Error::InfiniteLoop) = guard.patrol(map) {
             loops += 1;
         }
-        map.tiles[tile] = Tile::Ignored;
+        map[coords] = Tile::Ignored;
+        guard.restore(&start);
     }
 
     Ok(loops)
 }
 
+/// Patrols each map in `tasks` with its own guard, in parallel.
+///
+/// The results are returned in the same order as `tasks`, holding either the guard after its
+/// patrol or the error that stopped it.
+fn patrol_batch(tasks: Vec<(Guard, Map)>) -> Vec<Result<Guard>> {
+    tasks
+        .into_par_iter()
+        .map(|(mut guard, map)| guard.patrol(&map).map(|_| guard))
+        .collect()
+}
+
 fn main() -> result::Result<(), Box<dyn error::Error>> {
     let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
-    let data = fs::read_to_string(dataset)?;
-
-    let mut map = Map::new(&data)?;
+    let mut map = Map::from_path(&dataset)?;
+    map.validate()?;
     let mut guard = Guard::find(&map).ok_or(Error::NoGuard)?;
 
-    guard.patrol(&map)?;
+    let steps = guard.patrol(&map)?;
+    println!("Steps taken: {steps}");
     println!("Visited tiles: {}", guard.unique_visits().len());
 
     println!(
@@ -255,9 +1691,15 @@ fn main() -> result::Result<(), Box<dyn error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::mem;
+    use std::sync::mpsc;
 
     fn get_test_map() -> Map {
-        let s: String = vec![
+        Map::new(&get_test_map_str()).unwrap()
+    }
+
+    fn get_test_map_str() -> String {
+        vec![
             "....#.....\n",
             ".........#\n",
             "..........\n",
@@ -270,9 +1712,7 @@ mod tests {
             "......#...",
         ]
         .into_iter()
-        .collect();
-
-        Map::new(&s).unwrap()
+        .collect()
     }
 
     fn get_looping_map() -> Map {
@@ -302,7 +1742,7 @@ mod tests {
             g,
             Some(Guard {
                 direction: Direction::Up,
-                position: 64,
+                position: (6, 4),
                 ..Default::default()
             })
         );
@@ -317,15 +1757,375 @@ mod tests {
     }
 
     #[test]
-    fn guard_detects_infinite_loop() {
-        let m = get_looping_map();
+    fn guard_counts_patrol_steps() {
+        let m = get_test_map();
         let mut g = Guard::find(&m).unwrap();
+        let steps = g.patrol(&m).unwrap();
+        assert_eq!(steps, g.path_length() - 1);
+        // The path crosses itself, so some tiles are stepped on more than once.
+        assert_eq!(steps, 44);
+        assert_eq!(g.unique_visits().len() - 1, 40);
 
-        assert!(g.patrol(&m).is_err());
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(g.patrol(&m), Err(Error::InfiniteLoop));
+        assert!(g.path_length() > 0);
     }
 
     #[test]
-    fn guard_counts_visited_tiles() {
+    fn guard_exits_early_from_border() {
+        for m in [get_test_map(), get_looping_map()] {
+            let mut g = Guard::find(&m).unwrap();
+            loop {
+                let mut probe = g.clone();
+                let event = probe.advance(&m);
+                assert_eq!(
+                    g.can_exit_immediately(&m),
+                    matches!(event, GuardEvent::Exited(_))
+                );
+                if matches!(event, GuardEvent::Exited(_) | GuardEvent::LoopDetected(_)) {
+                    break;
+                }
+                g = probe;
+            }
+
+            let mut stepped = Guard::find(&m).unwrap();
+            let events: Vec<_> = stepped.stepper(&m).collect();
+            let mut patrolled = Guard::find(&m).unwrap();
+            let result = patrolled.patrol(&m);
+            assert_eq!(patrolled, stepped);
+            if let Ok(steps) = result {
+                let stepped_count = events
+                    .iter()
+                    .filter(|e| matches!(e, GuardEvent::Stepped(_)))
+                    .count();
+                assert_eq!(steps, stepped_count);
+            }
+        }
+
+        let m = Map::new("..^..").unwrap();
+        assert!(Guard::find(&m).unwrap().can_exit_immediately(&m));
+        let m = Map::new(".....\n..^..").unwrap();
+        assert!(!Guard::find(&m).unwrap().can_exit_immediately(&m));
+    }
+
+    #[test]
+    fn map_traces_guard_in_every_direction() {
+        let m = get_test_map();
+        let traces = m.guard_traces();
+        assert_eq!(traces.len(), 4);
+
+        let coverage: Vec<_> = traces
+            .iter()
+            .map(|t| t.as_ref().map(|g| g.unique_visits().len()))
+            .collect();
+        assert_eq!(coverage, [Ok(41), Ok(6), Ok(4), Ok(26)]);
+        assert_eq!(m.max_coverage_direction(), Some(Direction::Up));
+
+        // Without the obstacle above, going up exits straight away.
+        let mut m = m;
+        m.tiles[4] = Tile::Ignored;
+        assert_eq!(m.max_coverage_direction(), Some(Direction::Left));
+
+        let m = Map::new("...\n.#.").unwrap();
+        assert!(m.guard_traces().iter().all(|t| *t == Err(Error::NoGuard)));
+        assert_eq!(m.max_coverage_direction(), None);
+    }
+
+    #[test]
+    fn guard_records_direction_of_each_step() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            (64, Direction::Up), (54, Direction::Up), (44, Direction::Up), (34, Direction::Up),
+            (24, Direction::Up), (14, Direction::Right), (15, Direction::Right),
+            (16, Direction::Right), (17, Direction::Right), (18, Direction::Down),
+        ];
+        assert_eq!(g.visited[..expected.len()], expected);
+
+        // Every step moves one tile in the direction recorded for the tile left.
+        for w in g.visited.windows(2) {
+            let ((from, d), (to, _)) = (w[0], w[1]);
+            let (row, col) = m.coords_of(from);
+            let (dr, dc) = d.delta();
+            assert_eq!(m.index_of((row + dr, col + dc)), Some(to));
+        }
+    }
+
+    #[test]
+    fn guard_detects_infinite_loop() {
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+
+        assert!(g.patrol(&m).is_err());
+    }
+
+    /// Walks `map` with flat indices and signed offsets, returning the visited tiles.
+    fn walk_flat(map: &Map) -> HashSet<usize> {
+        let g = Guard::find(map).unwrap();
+        let (mut pos, mut d) = (g.flat_position(map), g.direction);
+        let mut visited = HashSet::new();
+        let mut seen = HashSet::new();
+        while seen.insert((pos, d)) {
+            visited.insert(pos);
+            #[allow(clippy::cast_possible_wrap)]
+            let offset = match d {
+                Direction::Up => -(map.width as isize),
+                Direction::Down => map.width as isize,
+                Direction::Left => -1,
+                Direction::Right => 1,
+            };
+            let Some(next) = pos.checked_add_signed(offset) else {
+                break;
+            };
+            let wrapped = matches!(d, Direction::Left | Direction::Right)
+                && next / map.width != pos / map.width;
+            if next >= map.tiles.len() || wrapped {
+                break;
+            }
+            if map.tiles[next] == Tile::Occupied {
+                d = d.turned();
+            } else {
+                pos = next;
+            }
+        }
+        visited
+    }
+
+    #[test]
+    fn guard_coordinates_match_flat_walk() {
+        for (m, looping) in [(get_test_map(), false), (get_looping_map(), true)] {
+            let mut g = Guard::find(&m).unwrap();
+            assert_eq!(g.patrol(&m).is_err(), looping);
+
+            let mut visited = g.unique_visits();
+            visited.insert(g.flat_position(&m));
+            assert_eq!(visited, walk_flat(&m));
+        }
+
+        let m = get_test_map();
+        assert_eq!(m.tile_at_position((6, 4)), Some(Tile::Guard(Direction::Up)));
+        assert_eq!(m.tile_at_position((0, 4)), Some(Tile::Occupied));
+        for outside in [(-1, 0), (0, -1), (10, 0), (0, 10)] {
+            assert_eq!(m.tile_at_position(outside), None);
+        }
+    }
+
+    #[test]
+    fn guard_reports_patrol_events() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        assert!(g.patrol_async(&m, tx).is_ok());
+        let events: Vec<GuardEvent> = rx.iter().collect();
+        assert_eq!(events.first(), Some(&GuardEvent::Stepped(54)));
+        assert_eq!(
+            events.last(),
+            Some(&GuardEvent::Exited(g.flat_position(&m)))
+        );
+        assert!(events.contains(&GuardEvent::Turned(Direction::Right)));
+    }
+
+    #[test]
+    fn guard_reports_infinite_loop_event() {
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let handle = std::thread::spawn(move || rx.iter().collect::<Vec<GuardEvent>>());
+        assert_eq!(g.patrol_async(&m, tx), Err(Error::InfiniteLoop));
+        let events = handle.join().unwrap();
+        assert!(matches!(events.last(), Some(GuardEvent::LoopDetected(_))));
+    }
+
+    #[test]
+    fn guard_finds_loop_entry_point() {
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(g.patrol(&m), Err(Error::InfiniteLoop));
+
+        let entry = g.loop_entry_point(&m).unwrap();
+        let period = g.loop_period(&m).unwrap();
+        assert_eq!(entry, 64);
+        assert_eq!(period, 18);
+
+        let first = g.visited.iter().position(|&(p, _)| p == entry).unwrap();
+        assert_eq!(g.visited[first + period].0, entry);
+
+        // The guard walks up from (5, 1) and joins the loop around (1..=3, 1..=3) at (3, 1),
+        // coming back to it from the right after 8 steps.
+        let m = Map::new(".#...\n....#\n.....\n#....\n...#.\n.^...").unwrap();
+        let mut g = Guard::find(&m).unwrap();
+        let start = g.flat_position(&m);
+        assert_eq!(g.patrol(&m), Err(Error::InfiniteLoop));
+
+        let entry = g.loop_entry_point(&m).unwrap();
+        assert_eq!(entry, 3 * m.width + 1);
+        assert_ne!(entry, start);
+        assert_eq!(g.loop_period(&m), Some(8));
+    }
+
+    #[test]
+    fn guard_without_loop_has_no_entry_point() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        assert!(g.loop_entry_point(&m).is_none());
+        assert!(g.loop_period(&m).is_none());
+    }
+
+    #[test]
+    fn guard_backtracks_last_step() {
+        let m = get_test_map();
+        let mut expected = Guard::find(&m).unwrap();
+        expected.patrol(&m).unwrap();
+
+        let mut g = Guard::find(&m).unwrap();
+        assert!(g.backtrack(&m).is_none());
+
+        // Walk past the first obstacle, then step back before it.
+        let events: Vec<GuardEvent> = g.stepper(&m).take(7).collect();
+        assert_eq!(events[5], GuardEvent::Turned(Direction::Right));
+        assert_eq!(g.position, (1, 5));
+
+        assert_eq!(g.backtrack(&m), Some(15));
+        assert_eq!((g.position, g.direction), ((1, 4), Direction::Right));
+        assert_eq!(g.backtrack(&m), Some(14));
+        assert_eq!((g.position, g.direction), ((2, 4), Direction::Up));
+        assert!(g.obstacles.is_empty());
+
+        let last = g.stepper(&m).last();
+        assert_eq!(last, Some(GuardEvent::Exited(expected.flat_position(&m))));
+        assert_eq!(g, expected);
+    }
+
+    #[test]
+    fn guard_path_has_spatial_metrics() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(g.path_length(), 0);
+        assert_eq!(g.bounding_box(&m), ((6, 4), (6, 4)));
+        assert_eq!(g.path_area(&m), 1);
+
+        g.patrol(&m).unwrap();
+        assert_eq!(g.bounding_box(&m), ((1, 1), (9, 8)));
+        assert_eq!(g.path_area(&m), 72);
+        assert!(g.unique_visits().len() <= g.path_area(&m));
+        assert!(g.path_length() >= g.unique_visits().len());
+    }
+
+    #[test]
+    fn guard_counts_visited_tiles_compactly() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+        let mut compact = Guard::find(&m).unwrap();
+        let visited = compact.patrol_compact(&m).unwrap();
+
+        assert_eq!(visited.count_ones(), 41);
+        assert_eq!(
+            visited
+                .iter()
+                .enumerate()
+                .filter_map(|(pos, v)| v.then_some(pos))
+                .collect::<HashSet<_>>(),
+            g.unique_visits()
+        );
+        assert!(compact.visited.is_empty());
+        assert_eq!(compact.position, g.position);
+
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(g.patrol_compact(&m), Err(Error::InfiniteLoop));
+    }
+
+    #[test]
+    fn guard_detects_loops_with_bloom_filter() {
+        // The textbook sizing for 1000 items at 1% is 9586 bits and 7 hash functions.
+        let filter = BloomFilter::with_rate(1000, 0.01);
+        assert_eq!(filter.bits.len(), 9586);
+        assert_eq!(filter.hashes, 7);
+
+        for m in [get_test_map(), get_looping_map()] {
+            let mut exact = Guard::find(&m).unwrap();
+            let mut bloom = exact.clone();
+            assert_eq!(bloom.patrol_bloom(&m), exact.patrol(&m).map(|_| ()));
+            assert_eq!(bloom.visited, exact.visited);
+        }
+
+        // Every obstacle placement tried by `count_loops` must agree with the exact patrol.
+        let mut m = get_test_map();
+        let base = Guard::find(&m).unwrap();
+        let mut g = base.clone();
+        g.patrol(&m).unwrap();
+
+        let mut false_positives = 0;
+        for tile in g.unique_visits() {
+            if tile == base.flat_position(&m) {
+                continue;
+            }
+            m.tiles[tile] = Tile::Occupied;
+            let exact = base.clone().patrol(&m);
+            let bloom = base.clone().patrol_bloom(&m);
+            if exact.is_ok() && bloom.is_err() {
+                false_positives += 1;
+            }
+            assert_eq!(bloom.is_err(), exact.is_err());
+            m.tiles[tile] = Tile::Ignored;
+        }
+        assert_eq!(false_positives, 0);
+    }
+
+    #[test]
+    fn guard_reports_obstacles_by_coordinates() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        let coords = g.obstacles_as_coordinates(&m);
+        assert_eq!(coords.len(), g.obstacles.len());
+        for ((row, col), directions) in &coords {
+            assert_eq!(g.obstacles[&(row * m.width + col)], *directions);
+        }
+
+        let summary = g.obstacles_summary(&m);
+        assert_eq!(summary.lines().count(), coords.len());
+        for (row, col) in coords.keys() {
+            assert!(summary.contains(&format!("(row={row}, col={col})")));
+        }
+        assert!(summary.starts_with("(row=0, col=4) approached from [Up]\n"));
+    }
+
+    #[test]
+    fn guard_patrol_respects_step_limit() {
+        // 99 steps to the last tile, then one more to exit.
+        let m = Map::new(&format!(">{}", ".".repeat(99))).unwrap();
+
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(
+            g.patrol_bounded(&m, 99),
+            Err(BoundedPatrolError::StepLimitExceeded(99))
+        );
+        assert_eq!(g.flat_position(&m), 99);
+
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(g.patrol_bounded(&m, 100), Ok(()));
+
+        let m = get_looping_map();
+        let mut g = Guard::find(&m).unwrap();
+        assert_eq!(
+            g.patrol_bounded(&m, usize::MAX),
+            Err(BoundedPatrolError::InfiniteLoop)
+        );
+    }
+
+    #[test]
+    fn guard_counts_visited_tiles() {
         let m = get_test_map();
         let mut g = Guard::find(&m).unwrap();
         g.patrol(&m).unwrap();
@@ -333,19 +2133,671 @@ mod tests {
         assert_eq!(g.unique_visits().len(), 41);
     }
 
+    #[test]
+    fn guard_maps_visited_tiles() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        let visits = g.unique_visits();
+        let grid = g.visited_grid(&m);
+        let mask = g.visited_mask(&m);
+        assert_eq!(grid.len(), m.height());
+        assert_eq!(grid.iter().flatten().filter(|&&v| v).count(), visits.len());
+        assert_eq!(
+            mask.iter().map(|&v| usize::from(v)).sum::<usize>(),
+            visits.len()
+        );
+
+        for (r, row) in grid.iter().enumerate() {
+            assert_eq!(row.len(), m.width);
+            for (c, &v) in row.iter().enumerate() {
+                assert_eq!(v, mask[r * m.width + c] == 1);
+                assert_eq!(v, visits.contains(&(r * m.width + c)));
+            }
+        }
+    }
+
     #[test]
     fn map_returns_correct_obstacle_count() {
         let m = get_test_map();
         assert_eq!(m.count_obstacles(), 8);
     }
 
+    #[test]
+    fn directions_rotate_arithmetically() {
+        assert_eq!(Direction::Up + 1, Direction::Right);
+        assert_eq!(Direction::Up + -1, Direction::Left);
+        assert_eq!(Direction::Up + 4, Direction::Up);
+        assert_eq!(Direction::Left + 2, Direction::Right);
+        assert_eq!(Direction::Down.opposite(), Direction::Up);
+        assert_eq!(Direction::Right.rotate_n(-6), Direction::Left);
+
+        let mut g = Guard::default();
+        let mut turns = vec![];
+        for _ in 0..5 {
+            g.turn();
+            turns.push(g.direction);
+        }
+        assert_eq!(
+            turns,
+            [
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+                Direction::Up,
+                Direction::Right
+            ]
+        );
+    }
+
+    #[test]
+    fn tiles_round_trip_through_chars() {
+        for c in ['.', '#', '^', 'v', '<', '>'] {
+            let t = Tile::try_from(c).unwrap();
+            assert_eq!(char::from(t), c);
+            assert_eq!(t.to_string(), c.to_string());
+        }
+    }
+
+    #[test]
+    fn map_round_trips_through_strings() {
+        let s = get_test_map_str();
+        let m = Map::from_str(&s).unwrap();
+
+        assert_eq!(m.to_string(), s);
+        assert_eq!(get_test_map().to_string(), s);
+    }
+
+    #[test]
+    fn map_prints_guard_path() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        let mut out = io::Cursor::new(vec![]);
+        m.print_path(&g, &mut out).unwrap();
+        let out = String::from_utf8(out.into_inner()).unwrap();
+        let marked = out.chars().filter(|&c| c == 'X' || c == 'v').count();
+
+        assert_eq!(marked, g.unique_visits().len());
+        assert_eq!(
+            out,
+            concat!(
+                "....#.....\n",
+                "....XXXXX#\n",
+                "....X...X.\n",
+                "..#.X...X.\n",
+                "..XXXXX#X.\n",
+                "..X.X.X.X.\n",
+                ".#XXXXXXX.\n",
+                ".XXXXXXX#.\n",
+                "#XXXXXXX..\n",
+                "......#v..\n",
+            )
+        );
+
+        let mut out = io::Cursor::new(vec![]);
+        m.print_path_html(&g, &mut out).unwrap();
+        let out = String::from_utf8(out.into_inner()).unwrap();
+
+        assert_eq!(
+            out.matches("class=\"visited\"").count() + 1,
+            g.unique_visits().len()
+        );
+        assert_eq!(out.matches("class=\"occupied\"").count(), 8);
+        assert_eq!(out.matches("class=\"guard\"").count(), 1);
+    }
+
+    #[test]
+    fn map_round_trips_through_rle() {
+        for m in [get_test_map(), get_looping_map()] {
+            assert_eq!(Map::from_rle(&m.to_rle(), m.width), Ok(m));
+        }
+
+        let m = Map::from_rle("4.#5.#^", 4).unwrap();
+        assert_eq!(m.to_string(), "....\n#...\n..#^");
+        assert_eq!(m.to_rle(), "4.#5.#^");
+    }
+
+    #[test]
+    fn map_rejects_malformed_rle() {
+        assert_eq!(Map::from_rle("3.x", 4), Err(RleError::InvalidTile('x')));
+        assert!(matches!(
+            Map::from_rle("99999999999999999999999.", 4),
+            Err(RleError::InvalidCount(_))
+        ));
+        assert_eq!(Map::from_rle("3.#2", 4), Err(RleError::DanglingCount));
+        assert_eq!(
+            Map::from_rle("99999999999999.", 4),
+            Err(RleError::TooManyTiles {
+                limit: 4 * Map::MAX_RLE_ROWS
+            })
+        );
+        assert_eq!(
+            Map::from_rle(&format!("{}.3.", 4 * Map::MAX_RLE_ROWS - 2), 4),
+            Err(RleError::TooManyTiles {
+                limit: 4 * Map::MAX_RLE_ROWS
+            })
+        );
+        let m = Map::from_rle(&format!("{}.", 4 * Map::MAX_RLE_ROWS), 4).unwrap();
+        assert_eq!(m.height(), Map::MAX_RLE_ROWS);
+        assert_eq!(
+            Map::from_rle("5.", 4),
+            Err(RleError::LengthMismatch {
+                expected: 8,
+                got: 5
+            })
+        );
+    }
+
+    #[test]
+    fn map_finds_border_tiles() {
+        let m = get_test_map();
+        let border: HashSet<usize> = m.border_positions().collect();
+
+        assert_eq!(border.len(), 36);
+        assert!(border.iter().all(|&pos| m.is_border_tile(pos)));
+        assert!((0..m.tiles.len())
+            .filter(|pos| !border.contains(pos))
+            .all(|pos| !m.is_border_tile(pos)));
+        assert!(!m.is_border_tile(64));
+    }
+
+    #[test]
+    fn guard_history_round_trips_through_csv() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        let csv = g.to_csv(&m);
+        assert!(csv.starts_with("0,64,6,4,^\n"));
+
+        let parsed = Guard::from_csv(&csv, &m).unwrap();
+        assert_eq!(parsed.visited, g.visited);
+        assert_eq!(parsed.unique_visits(), g.unique_visits());
+        assert_eq!(parsed.position, g.position);
+        assert_eq!(parsed.direction, g.direction);
+        assert!(parsed.obstacles.is_empty());
+    }
+
+    #[test]
+    fn guard_history_rejects_malformed_csv() {
+        let m = get_test_map();
+
+        assert_eq!(
+            Guard::from_csv("0,64,6,4", &m),
+            Err(ParseError::MissingColumn)
+        );
+        assert_eq!(
+            Guard::from_csv("0,64,6,4,^,^", &m),
+            Err(ParseError::ExtraColumn)
+        );
+        assert_eq!(
+            Guard::from_csv("1,64,6,4,^", &m),
+            Err(ParseError::StepOutOfOrder {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Guard::from_csv("0,64,6,4,^\n0,54,5,4,^", &m),
+            Err(ParseError::StepOutOfOrder {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert!(matches!(
+            Guard::from_csv("x,64,6,4,^", &m),
+            Err(ParseError::MalformedInteger(_))
+        ));
+        assert_eq!(
+            Guard::from_csv("0,100,10,0,^", &m),
+            Err(ParseError::IndexOutOfRange(100))
+        );
+        assert_eq!(
+            Guard::from_csv("0,64,6,5,^", &m),
+            Err(ParseError::IndexOutOfRange(64))
+        );
+        // (row 0, col 11) does not exist, even if its flat index does.
+        assert_eq!(
+            Guard::from_csv("0,11,0,11,^", &m),
+            Err(ParseError::IndexOutOfRange(11))
+        );
+        assert_eq!(
+            Guard::from_csv(&format!("0,64,{},4,^", usize::MAX), &m),
+            Err(ParseError::IndexOutOfRange(64))
+        );
+        assert_eq!(
+            Guard::from_csv(&format!("0,64,6,{},^", usize::MAX), &m),
+            Err(ParseError::IndexOutOfRange(64))
+        );
+        assert!(matches!(
+            Guard::from_csv("0,x,6,4,^", &m),
+            Err(ParseError::MalformedInteger(_))
+        ));
+        assert_eq!(
+            Guard::from_csv("0,64,6,4,#", &m),
+            Err(ParseError::InvalidDirection)
+        );
+    }
+
+    #[test]
+    fn map_validates_guard_count() {
+        let m = get_test_map();
+        assert_eq!(m.guard_count(), 1);
+        assert_eq!(m.validate(), Ok(()));
+
+        let m = Map::new("...\n.#.\n...").unwrap();
+        assert_eq!(m.guard_count(), 0);
+        assert_eq!(m.validate(), Err(MapValidationError::NoGuard));
+
+        let m = Map::new("^..\n.#.\n..>").unwrap();
+        assert_eq!(m.guard_count(), 2);
+        assert_eq!(m.validate(), Err(MapValidationError::MultipleGuards(2)));
+
+        let m = Map {
+            tiles: Map::new("^...#").unwrap().tiles,
+            width: 3,
+        };
+        assert_eq!(
+            m.validate(),
+            Err(MapValidationError::UnevenRows { tiles: 5, width: 3 })
+        );
+    }
+
+    #[test]
+    fn map_rejects_ragged_rows() {
+        assert_eq!(
+            Map::new("^..\n.#"),
+            Err(Error::InvalidMap(MapValidationError::RaggedRow {
+                row: 1,
+                len: 2,
+                width: 3
+            }))
+        );
+
+        // The total amount of tiles is a multiple of the first row's width.
+        assert_eq!(
+            Map::new("^..\n.#\n...."),
+            Err(Error::InvalidMap(MapValidationError::RaggedRow {
+                row: 1,
+                len: 2,
+                width: 3
+            }))
+        );
+
+        // A trailing newline does not count as a row.
+        assert_eq!(Map::new("^..\n.#.\n"), Map::new("^..\n.#."));
+    }
+
+    #[test]
+    fn map_is_built_from_rows() {
+        for m in [get_test_map(), get_looping_map()] {
+            assert_eq!(Map::from_rows(m.rows()), Ok(m.clone()));
+            assert_eq!(Map::from_flat(m.tiles.clone(), m.width), Ok(m));
+        }
+
+        let mut rows = get_test_map().rows();
+        rows[3].pop();
+        assert_eq!(
+            Map::from_rows(rows),
+            Err(MapValidationError::RaggedRow {
+                row: 3,
+                len: 9,
+                width: 10
+            })
+        );
+        assert_eq!(
+            Map::from_flat(get_test_map().tiles, 7),
+            Err(MapValidationError::UnevenRows {
+                tiles: 100,
+                width: 7
+            })
+        );
+        assert_eq!(
+            Map::from_rows(vec![]),
+            Err(MapValidationError::UnevenRows { tiles: 0, width: 0 })
+        );
+    }
+
+    #[test]
+    fn map_rejects_trapped_guard() {
+        let m = Map::new(".#.\n#^#\n.#.").unwrap();
+        assert_eq!(
+            m.validate(),
+            Err(MapValidationError::GuardTrapped { position: 4 })
+        );
+
+        // The guard can leave the map from its edge.
+        let m = Map::new("#^#\n.#.").unwrap();
+        assert_eq!(m.validate(), Ok(()));
+        let m = Map::new(".#.\n#^.\n.#.").unwrap();
+        assert_eq!(m.validate(), Ok(()));
+    }
+
+    #[test]
+    fn map_is_read_from_path() {
+        let err = Map::from_path(Path::new("nonexistent/input.txt")).unwrap_err();
+        assert!(matches!(err, MapFromPathError::Io(ref e) if e.kind() == io::ErrorKind::NotFound));
+
+        let path = std::env::temp_dir().join(format!("day6-map-{}.txt", std::process::id()));
+        fs::write(&path, get_test_map_str()).unwrap();
+        let m = Map::from_path(&path);
+        fs::write(&path, "..x\n...").unwrap();
+        let err = Map::from_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(m.unwrap(), Map::new(&get_test_map_str()).unwrap());
+        assert!(matches!(
+            err,
+            Err(MapFromPathError::Map(Error::InvalidTile))
+        ));
+    }
+
+    #[test]
+    fn map_breaks_down_tile_count() {
+        let m = get_test_map();
+        let breakdown = m.tile_count();
+        assert_eq!(
+            breakdown,
+            TileBreakdown {
+                ignored: 91,
+                occupied: 8,
+                guard: 1
+            }
+        );
+        assert_eq!(breakdown.occupied, m.count_obstacles());
+        assert_eq!(breakdown.total(), m.tiles.len());
+
+        let m = get_looping_map();
+        let breakdown = m.tile_count();
+        assert_eq!(
+            breakdown,
+            TileBreakdown {
+                ignored: 90,
+                occupied: 9,
+                guard: 1
+            }
+        );
+        assert_eq!(breakdown.occupied, m.count_obstacles());
+        assert_eq!(breakdown.total(), m.tiles.len());
+        assert_eq!(
+            breakdown.to_string(),
+            "Ignored:      90\nOccupied:      9\nGuard:         1\nTotal:       100"
+        );
+    }
+
+    #[test]
+    fn flood_fill_reaches_all_connected_tiles() {
+        let m = get_test_map();
+        let components = m.connected_components();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), m.tiles.len() - m.count_obstacles());
+        assert_eq!(m.flood_fill(0), components[0]);
+        assert!(m.flood_fill(4).is_empty());
+        assert!(m.flood_fill(m.tiles.len()).is_empty());
+
+        let m = Map::new(".#.\n#..\n^..").unwrap();
+        let components = m.connected_components();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0], HashSet::from([0]));
+        assert_eq!(components[1], HashSet::from([2, 4, 5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn patrol_batch_matches_sequential_patrols() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Error>();
+
+        let maps = [
+            get_test_map(),
+            get_looping_map(),
+            get_test_map(),
+            get_looping_map(),
+        ];
+        let tasks: Vec<_> = maps
+            .iter()
+            .map(|m| (Guard::find(m).unwrap(), m.clone()))
+            .collect();
+        let expected: Vec<_> = tasks
+            .iter()
+            .map(|(g, m)| {
+                let mut g = g.clone();
+                g.patrol(m).map(|_| g)
+            })
+            .collect();
+
+        let results = patrol_batch(tasks);
+        assert_eq!(results, expected);
+        assert_eq!(results[0].as_ref().unwrap().unique_visits().len(), 41);
+        assert_eq!(results[1], Err(Error::InfiniteLoop));
+        assert!(patrol_batch(vec![]).is_empty());
+    }
+
+    #[test]
+    fn map_is_indexed_by_coordinates() {
+        let mut m = get_test_map();
+        for r in 0..m.height() {
+            for c in 0..m.width {
+                assert_eq!(m[(r, c)], m.tiles[r * m.width + c]);
+            }
+        }
+        assert_eq!(m[(0, 4)], Tile::Occupied);
+        assert_eq!(m[(6, 4)], Tile::Guard(Direction::Up));
+
+        m[(0, 0)] = Tile::Occupied;
+        assert_eq!(m.tiles[0], Tile::Occupied);
+        assert_eq!(m[(0, 0)], Tile::Occupied);
+    }
+
+    #[test]
+    #[should_panic(expected = "coordinates (3, 10) out of bounds for 10x10 map")]
+    fn map_index_panics_out_of_bounds() {
+        let m = get_test_map();
+        let _ = m[(3, 10)];
+    }
+
+    #[test]
+    fn patrol_path_is_drawn_as_svg() {
+        let m = get_test_map();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+
+        let svg = g.path_as_svg(&m, 10);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains(r#"viewBox="0 0 100 100""#));
+        assert_eq!(svg.matches("<rect").count(), m.tiles.len());
+        assert_eq!(
+            svg.matches(r##"fill="#333333""##).count(),
+            m.count_obstacles()
+        );
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert_eq!(svg.matches(r#"fill="blue""#).count(), 1);
+        assert!(svg.contains(r#"<circle cx="45" cy="65" r="2" fill="blue"/>"#));
+
+        // The path crosses itself at (row=6, col=4) and (row=6, col=6), among others.
+        assert!(svg.contains(r#"<circle cx="45" cy="65" r="2" fill="red"/>"#));
+        assert!(svg.contains(r#"<circle cx="65" cy="65" r="2" fill="red"/>"#));
+    }
+
+    #[test]
+    fn map_counts_exact_tiles() {
+        assert_eq!(Tile::default(), Tile::Ignored);
+
+        for m in [get_test_map(), get_looping_map()] {
+            let ignored = m.count_tile(Tile::Ignored);
+            let occupied = m.count_tile(Tile::Occupied);
+            let guard = m.count_tile(Tile::Guard(Direction::Up));
+            assert_eq!(ignored + occupied + guard, m.tiles.len());
+            assert_eq!(occupied, m.count_obstacles());
+            assert_eq!(m.count_tile(Tile::Guard(Direction::Down)), 0);
+
+            let frequencies = m.tile_frequencies();
+            let mut keys: Vec<_> = frequencies.keys().copied().collect();
+            keys.sort_unstable();
+            assert_eq!(keys, ["guard", "ignored", "occupied"]);
+            assert_eq!(frequencies["ignored"], ignored);
+            assert_eq!(frequencies["occupied"], occupied);
+            assert_eq!(frequencies["guard"], 1);
+        }
+    }
+
     #[test]
     fn possible_infinite_loops_are_found() {
         let mut m = get_test_map();
         let mut g = Guard::find(&m).unwrap();
         g.patrol(&m).unwrap();
 
-        let unique_tiles = g.visited.iter().copied().collect::<HashSet<_>>();
+        let unique_tiles = g.visited.iter().map(|&(p, _)| p).collect::<HashSet<_>>();
         assert_eq!(count_loops(unique_tiles, &mut m), Ok(6));
     }
+
+    #[test]
+    fn last_tile_is_visited_when_exiting_through_any_edge() {
+        for (s, last) in [
+            ("...\n.^.\n...", 1),
+            ("...\n.v.\n...", 7),
+            ("...\n.<.\n...", 3),
+            ("...\n.>.\n...", 5),
+        ] {
+            let m = Map::new(s).unwrap();
+            let mut g = Guard::find(&m).unwrap();
+            assert_eq!(g.patrol(&m), Ok(1));
+            assert_eq!(g.visited.last().map(|&(p, _)| p), Some(last), "{s}");
+            assert_eq!(g.unique_visits(), HashSet::from([4, last]), "{s}");
+
+            let mut g = Guard::find(&m).unwrap();
+            assert_eq!(g.patrol_bounded(&m, 10), Ok(()));
+            assert_eq!(g.unique_visits(), HashSet::from([4, last]), "{s}");
+        }
+
+        // The exit tile is a candidate for obstacles too.
+        let m = Map::new("....\n.^..\n....\n....").unwrap();
+        assert_eq!(m.minimum_obstacles_to_trap(4), 4);
+    }
+
+    #[test]
+    fn minimum_obstacles_to_trap_are_found() {
+        assert_eq!(get_test_map().minimum_obstacles_to_trap(3), 1);
+        assert_eq!(get_looping_map().minimum_obstacles_to_trap(3), 0);
+
+        // The guard walks straight off the map, and needs four turns to walk in circles.
+        let m = Map::new("....\n....\n.v..\n....").unwrap();
+        assert_eq!(m.minimum_obstacles_to_trap(3), usize::MAX);
+        assert_eq!(m.minimum_obstacles_to_trap(4), 4);
+    }
+
+    #[test]
+    fn map_clones_are_independent() {
+        let m = get_test_map();
+        let mut c = m.clone();
+        assert_eq!(c, m);
+
+        c.tiles[0] = Tile::Occupied;
+        assert_ne!(c, m);
+        assert_eq!(m.tiles[0], Tile::Ignored);
+        assert_eq!(m, get_test_map());
+    }
+
+    #[test]
+    fn snapshots_are_restored() {
+        let mut m = get_test_map();
+        let original = m.clone();
+        let mut g = Guard::find(&m).unwrap();
+
+        let map_snap = m.snapshot();
+        let outer = g.snapshot();
+        g.stepper(&m).take(10).for_each(drop);
+        let visited_len = g.visited.len();
+        let inner = g.snapshot();
+
+        m.tiles[0] = Tile::Occupied;
+        let nested = m.snapshot();
+        m.tiles[63] = Tile::Occupied;
+        g.patrol(&m).unwrap_err();
+        assert!(g.looped);
+
+        g.restore(&inner);
+        m.restore(&nested);
+        assert_eq!(g.visited.len(), visited_len);
+        assert!(!g.looped);
+        assert_eq!(m.tiles[63], Tile::Ignored);
+        assert_eq!(m.tiles[0], Tile::Occupied);
+
+        g.restore(&outer);
+        m.restore(&map_snap);
+        assert_eq!(m, original);
+        assert_eq!(g, Guard::find(&m).unwrap());
+    }
+
+    #[test]
+    #[ignore = "benchmark, needs the puzzle input"]
+    fn patrol_compact_footprint() {
+        let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
+        let m = Map::new(&fs::read_to_string(dataset).unwrap()).unwrap();
+
+        let mut g = Guard::find(&m).unwrap();
+        let (steps, elapsed) = aoc2024::time(|| g.patrol(&m).unwrap());
+        let indexed = g.visited.capacity() * mem::size_of::<(usize, Direction)>();
+        println!("Patrolled {steps} steps recording visits in {elapsed:?}, using {indexed} bytes");
+
+        let mut compact = Guard::find(&m).unwrap();
+        let (visited, elapsed) = aoc2024::time(|| compact.patrol_compact(&m).unwrap());
+        let bits = mem::size_of_val(visited.storage());
+        println!("Patrolled marking visits in {elapsed:?}, using {bits} bytes");
+
+        assert_eq!(
+            usize::try_from(visited.count_ones()).unwrap(),
+            g.unique_visits().len()
+        );
+        assert!(bits < indexed);
+    }
+
+    #[test]
+    #[ignore = "benchmark, needs the puzzle input"]
+    fn count_loops_throughput() {
+        let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
+        let mut m = Map::new(&fs::read_to_string(dataset).unwrap()).unwrap();
+        let mut g = Guard::find(&m).unwrap();
+        g.patrol(&m).unwrap();
+        let tiles = g.unique_visits();
+
+        let (restored, elapsed) = aoc2024::time(|| count_loops(tiles.clone(), &mut m).unwrap());
+        println!("Counted {restored} loops restoring snapshots in {elapsed:?}");
+
+        let base = Guard::find(&m).unwrap();
+        let (cloned, elapsed) = aoc2024::time(|| {
+            let mut loops = 0;
+            for &tile in &tiles {
+                if tile == base.flat_position(&m) {
+                    continue;
+                }
+                m.tiles[tile] = Tile::Occupied;
+                loops += usize::from(base.clone().patrol(&m).is_err());
+                m.tiles[tile] = Tile::Ignored;
+            }
+            loops
+        });
+        println!("Counted {cloned} loops cloning guards in {elapsed:?}");
+        assert_eq!(restored, cloned);
+    }
+
+    #[test]
+    #[ignore = "benchmark, needs the puzzle input"]
+    fn map_clone_throughput() {
+        let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
+        let m = Map::new(&fs::read_to_string(dataset).unwrap()).unwrap();
+
+        let runs = 10_000;
+        let (tiles, elapsed) =
+            aoc2024::time(|| (0..runs).map(|_| m.clone().tiles.len()).sum::<usize>());
+        assert_eq!(tiles, m.tiles.len() * runs);
+        println!(
+            "Cloned {runs} maps of {} tiles in {elapsed:?} ({:.0} clones/s)",
+            m.tiles.len(),
+            f64::from(u32::try_from(runs).unwrap()) / elapsed.as_secs_f64()
+        );
+    }
 }
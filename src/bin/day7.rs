@@ -1,42 +1,131 @@
 #![warn(clippy::pedantic)]
 #![allow(dead_code)]
 
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::error;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use indexmap::IndexSet;
+use rayon::prelude::*;
+
 /// Possible errors for this program.
 #[derive(Debug)]
 enum Error {
     /// The equation is too short.
     EquationTooShort,
+    /// The equation has a result but no values.
+    EmptyValues,
     /// An equation operand is malformed.
     MalformedOperand(ParseIntError),
+    /// An equation cannot be split at the given value index.
+    SplitOutOfRange { index: usize, len: usize },
+    /// A result does not fit in 64 bits.
+    Overflow,
+    /// An addend exceeds the total it should be subtracted from.
+    UnderflowInValidation,
+    /// The equations could not be read.
+    Io(io::Error),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::EquationTooShort => write!(f, "equation too short"),
+            Error::EmptyValues => write!(f, "equation without values"),
             Error::MalformedOperand(e) => write!(f, "malformed operand: {e}"),
+            Error::SplitOutOfRange { index, len } => {
+                write!(f, "cannot split {len} values at index {index}")
+            }
+            Error::Overflow => write!(f, "result overflow"),
+            Error::UnderflowInValidation => write!(f, "addend exceeds total"),
+            Error::Io(e) => write!(f, "cannot read equations: {e}"),
         }
     }
 }
 
 impl error::Error for Error {}
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// An error found while reading a batch of equations.
+#[derive(Debug)]
+struct BatchError {
+    /// Number of the offending line, starting from 1.
+    line: usize,
+    /// The error found in the line.
+    error: Error,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 impl From<ParseIntError> for Error {
     fn from(e: ParseIntError) -> Self {
         Self::MalformedOperand(e)
     }
 }
 
+/// Possible errors when verifying a sequence of operators.
+#[derive(Debug, PartialEq)]
+enum VerifyError {
+    /// The amount of operators does not fit the amount of values.
+    WrongLength { expected: usize, got: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::WrongLength { expected, got } => {
+                write!(f, "expected {expected} operators, got {got}")
+            }
+        }
+    }
+}
+
+impl error::Error for VerifyError {}
+
+/// An operator that can be placed between the values of an equation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Mul,
+    Concat,
+}
+
+impl Op {
+    /// Applies `self` to `x` and `y`, returning `None` on overflow.
+    fn apply(self, x: u64, y: u64) -> Option<u64> {
+        match self {
+            Op::Add => x.checked_add(y),
+            Op::Mul => x.checked_mul(y),
+            Op::Concat => join(x, y, 10),
+        }
+    }
+}
+
 /// An equation with a result and some values.
-#[derive(Debug)]
+///
+/// Equations are ordered by their result first, and by their values when results are equal.
+#[derive(Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct Equation {
     /// The expected result of this equation.
     result: u64,
@@ -44,6 +133,24 @@ struct Equation {
     values: Vec<u64>,
 }
 
+impl fmt::Display for Equation {
+    /// Formats `self` like a line of puzzle input, as in `190: 10 19`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.result)?;
+        for v in &self.values {
+            write!(f, " {v}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Equation {
+    /// Formats `self` as its result followed by the list of its values, as in `190 <- [10, 19]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <- {:?}", self.result, self.values)
+    }
+}
+
 impl FromStr for Equation {
     type Err = Error;
 
@@ -51,57 +158,447 @@ impl FromStr for Equation {
         let s = s.replace(':', "");
         let mut parts = s.split(' ').map(str::parse);
         let result = parts.next().ok_or(Error::EquationTooShort)??;
-        let values = parts.collect::<Result<_, _>>()?;
+        let values: Vec<u64> = parts.collect::<Result<_, _>>()?;
+        if values.is_empty() {
+            return Err(Error::EmptyValues);
+        }
 
         Ok(Self { result, values })
     }
 }
 
 impl Equation {
+    /// Reads the equations in the file at `path`, one per line, skipping the malformed ones.
+    ///
+    /// The errors found are returned alongside the number of the line they were found in,
+    /// starting from 1. If the file cannot be opened, the error is reported for line 0.
+    fn batch_from_file(path: &Path) -> (Vec<Equation>, Vec<(usize, Error)>) {
+        match File::open(path) {
+            Ok(f) => Self::from_reader_lenient(BufReader::new(f)),
+            Err(e) => (vec![], vec![(0, e.into())]),
+        }
+    }
+
+    /// Reads the equations in the file at `path`, one per line.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error found, along with the number of its line.
+    fn batch_from_file_strict(path: &Path) -> Result<Vec<Equation>, BatchError> {
+        let f = File::open(path).map_err(|e| BatchError {
+            line: 0,
+            error: e.into(),
+        })?;
+        Self::from_reader(BufReader::new(f))
+    }
+
+    /// Reads the equations in `reader`, one per line, skipping the malformed ones.
+    ///
+    /// The errors found are returned alongside the number of the line they were found in,
+    /// starting from 1.
+    fn from_reader_lenient<R: BufRead>(reader: R) -> (Vec<Equation>, Vec<(usize, Error)>) {
+        let mut eqs = vec![];
+        let mut errors = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            match line
+                .map_err(Error::from)
+                .and_then(|l| Equation::from_str(&l))
+            {
+                Ok(e) => eqs.push(e),
+                Err(e) => errors.push((i + 1, e)),
+            }
+        }
+
+        (eqs, errors)
+    }
+
+    /// Reads the equations in `reader`, one per line.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error found, along with the number of its line.
+    fn from_reader<R: BufRead>(reader: R) -> Result<Vec<Equation>, BatchError> {
+        let mut eqs = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let eq = line
+                .map_err(Error::from)
+                .and_then(|l| Equation::from_str(&l))
+                .map_err(|error| BatchError { line: i + 1, error })?;
+            eqs.push(eq);
+        }
+
+        Ok(eqs)
+    }
+
+    /// Reads all the lines in `reader`, then parses them as equations in parallel.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error found, along with the number of its line, just like `from_reader`.
+    fn from_reader_parallel<R: BufRead + Send>(reader: R) -> Result<Vec<Equation>, BatchError> {
+        let mut lines = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            lines.push(line.map_err(|e| BatchError {
+                line: i + 1,
+                error: e.into(),
+            })?);
+        }
+
+        // Collecting into a `Result` directly would return whichever error is found first by
+        // the threads, rather than the one in the earliest line.
+        let eqs: Vec<_> = lines
+            .par_iter()
+            .enumerate()
+            .map(|(i, l)| Equation::from_str(l).map_err(|error| BatchError { line: i + 1, error }))
+            .collect();
+        eqs.into_iter().collect()
+    }
+
     /// Determines if the values in `self` can produce its result.
     fn is_valid(&self) -> bool {
-        if (self.values.is_empty() && self.result == 1)
-            || (self.values.len() == 1 && self.result == self.values[0])
-        {
-            return true;
+        self.is_valid_iterative()
+    }
+
+    /// Determines if the values in `self` can produce its result, walking back from the last
+    /// value with an explicit stack instead of recursion.
+    ///
+    /// Each entry on the stack is a result and the amount of leading values left to produce it.
+    /// Entries already explored are skipped, since they always lead to the same answer.
+    fn is_valid_iterative(&self) -> bool {
+        if let Some(valid) = self.check_prefix(self.result, self.values.len()) {
+            return valid;
         }
 
-        let mut total = self.result;
-        for (i, v) in self.values.iter().rev().enumerate() {
-            if *v > total {
-                return false;
+        let mut stack = vec![(self.result, self.values.len())];
+        let mut seen = HashSet::new();
+        while let Some((total, len)) = stack.pop() {
+            if !seen.insert((total, len)) {
+                continue;
             }
 
-            if total % v == 0 {
-                // Since `v` is a divisor, let's try that possible path first.
-                let sub_eq = Equation {
-                    result: total / v,
-                    values: self.values[..self.values.len() - (i + 1)].to_vec(),
-                };
-                if sub_eq.is_valid() {
+            let v = self.values[len - 1];
+            if len == 1 {
+                // The first value must match whatever is left of the result.
+                if total == v {
                     return true;
                 }
+                continue;
+            }
+            if v > total {
+                continue;
             }
 
-            if let Some(n) = disjoin(total, *v) {
-                // Since `total` could be disjoined, let's try this path too.
-                let sub_eq = Equation {
-                    result: n,
-                    values: self.values[..self.values.len() - (i + 1)].to_vec(),
-                };
-                if sub_eq.is_valid() {
-                    return true;
-                }
+            // Using `v` as addend is tried last.
+            if let Ok(n) = subtract(total, v) {
+                stack.push((n, len - 1));
             }
 
-            // Last ditch attempt to validate by using `v` as subtrahend.
-            total -= *v;
+            let divided = total.is_multiple_of(v).then(|| total / v);
+            for n in [disjoin(total, v), divided].into_iter().flatten() {
+                match self.check_prefix(n, len - 1) {
+                    Some(true) => return true,
+                    Some(false) => {}
+                    None => stack.push((n, len - 1)),
+                }
+            }
         }
 
         false
     }
+
+    /// Checks if the first `len` values in `self` can obviously produce `total` or not.
+    ///
+    /// If the answer requires trying out operators, `None` is returned.
+    fn check_prefix(&self, total: u64, len: usize) -> Option<bool> {
+        let eq = Equation {
+            result: total,
+            values: self.values[..len].to_vec(),
+        };
+        if eq.quick_reject() {
+            return Some(false);
+        }
+
+        match eq.values[..] {
+            [] => Some(false),
+            [v] => Some(total == v),
+            _ if eq.join_values() == Some(total) => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Determines if the values in `self` can produce its result, recursing into shorter
+    /// equations.
+    fn is_valid_recursive(&self) -> bool {
+        if self.quick_reject() {
+            return false;
+        }
+
+        let Some((&v, rest)) = self.values.split_last() else {
+            return false;
+        };
+        if rest.is_empty() {
+            // The first value must match whatever is left of the result.
+            return self.result == v;
+        }
+
+        if self.join_values() == Some(self.result) {
+            // Concatenating every value already produces the result.
+            return true;
+        }
+
+        if v > self.result {
+            return false;
+        }
+
+        if self.result.is_multiple_of(v) {
+            // Since `v` is a divisor, let's try that possible path first.
+            let sub_eq_mul = Equation {
+                result: self.result / v,
+                values: rest.to_vec(),
+            };
+            if sub_eq_mul.is_valid_recursive() {
+                return true;
+            }
+        }
+
+        if let Some(n) = disjoin(self.result, v) {
+            // Since the result could be disjoined, let's try this path too.
+            let sub_eq_concat = Equation {
+                result: n,
+                values: rest.to_vec(),
+            };
+            if sub_eq_concat.is_valid_recursive() {
+                return true;
+            }
+        }
+
+        // Last ditch attempt to validate by using `v` as addend.
+        subtract(self.result, v).is_ok_and(|n| {
+            let sub_eq_add = Equation {
+                result: n,
+                values: rest.to_vec(),
+            };
+            sub_eq_add.is_valid_recursive()
+        })
+    }
+
+    /// Returns a copy of `self` with its result and all its values multiplied by `factor`.
+    ///
+    /// Scaling preserves the validity of equations relying only on addition, but not in general:
+    /// multiplications scale by `factor` once per operand, and concatenations shift digits
+    /// instead of scaling. If any multiplication overflows, `None` is returned.
+    fn scale(&self, factor: u64) -> Option<Equation> {
+        let result = self.result.checked_mul(factor)?;
+        let values = self
+            .values_iter()
+            .map(|v| v.checked_mul(factor))
+            .collect::<Option<_>>()?;
+
+        Some(Equation { result, values })
+    }
+
+    /// Determines if `self`, scaled by `factor`, is valid.
+    ///
+    /// If scaling overflows, the equation is considered invalid.
+    fn is_valid_scaled(&self, factor: u64) -> bool {
+        self.scale(factor).is_some_and(|e| e.is_valid())
+    }
+
+    /// Checks if placing `ops` between the values in `self` produces its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is not exactly one operator less than there are values.
+    fn verify(&self, ops: &[Op]) -> Result<bool, VerifyError> {
+        verify_strict(self.result, &self.values, ops)
+    }
+
+    /// Returns every sequence of operators that, placed between the values in `self`, produces
+    /// its result.
+    fn all_valid_sequences(&self) -> Vec<Vec<Op>> {
+        const OPS: [Op; 3] = [Op::Add, Op::Mul, Op::Concat];
+
+        let mut sequences = vec![vec![]];
+        for _ in 0..self.operator_count() {
+            sequences = sequences
+                .into_iter()
+                .flat_map(|seq| {
+                    OPS.into_iter().map(move |op| {
+                        let mut seq = seq.clone();
+                        seq.push(op);
+                        seq
+                    })
+                })
+                .collect();
+        }
+
+        sequences
+            .into_iter()
+            .filter(|ops| self.verify(ops) == Ok(true))
+            .collect()
+    }
+
+    /// Returns a key ordering equations by difficulty, so that those with fewer valid sequences of
+    /// operators come first.
+    fn by_difficulty(&self) -> impl Ord {
+        self.all_valid_sequences().len()
+    }
+
+    /// Returns the amount of values in `self`.
+    fn value_count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the amount of operators needed between the values in `self`.
+    fn operator_count(&self) -> usize {
+        self.values.len().saturating_sub(1)
+    }
+
+    /// Splits the values in `self` before the one at `index`.
+    ///
+    /// The result of each half depends on the operators placed between its values, so only the
+    /// values are returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either half would be empty, that is unless
+    /// `1 <= index <= self.value_count() - 1`.
+    fn split_at(&self, index: usize) -> Result<(&[u64], &[u64]), Error> {
+        if index == 0 || index >= self.values.len() {
+            return Err(Error::SplitOutOfRange {
+                index,
+                len: self.values.len(),
+            });
+        }
+        Ok(self.values.split_at(index))
+    }
+
+    /// Joins the values of `left` and `right`, combining their results with `op`.
+    ///
+    /// Since operators are evaluated left to right, the merged equation is valid whenever both
+    /// halves are and `right` has a single value. With more values, `right`'s result stops being
+    /// what its values evaluate to once `left`'s result precedes them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if combining the results overflows.
+    fn merge(left: &Equation, op: Op, right: &Equation) -> Result<Equation, Error> {
+        let result = op.apply(left.result, right.result).ok_or(Error::Overflow)?;
+        let values = left.values_iter().chain(right.values_iter()).collect();
+        Ok(Equation { result, values })
+    }
+
+    /// Checks if `self` needs no operators, so that its result is compared to its value directly.
+    fn is_trivial(&self) -> bool {
+        self.value_count() <= 1
+    }
+
+    /// Computes the smallest result the values in `self` can produce, evaluating left to right.
+    ///
+    /// This is the sum of the values, unless multiplying by 0 or 1 produces less. Since every
+    /// operator grows with its left operand, picking the smallest outcome at each step is enough.
+    /// An equation without values produces 0, and overflowing evaluations saturate.
+    fn min_result_possible(&self) -> u64 {
+        let Some((first, rest)) = self.values.split_first() else {
+            return 0;
+        };
+        rest.iter().fold(*first, |acc, v| {
+            [Op::Add, Op::Mul, Op::Concat]
+                .into_iter()
+                .filter_map(|op| op.apply(acc, *v))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+    }
+
+    /// Returns an iterator over the values in `self`.
+    fn values_iter(&self) -> impl DoubleEndedIterator<Item = u64> + ExactSizeIterator + '_ {
+        self.values.iter().copied()
+    }
+
+    /// Determines if `self` obviously cannot produce its result.
+    ///
+    /// This is the case when the result is less than every value, since no operator can produce
+    /// a smaller number than its operands. An equation without values is never rejected.
+    fn quick_reject(&self) -> bool {
+        self.values_iter().min().is_some_and(|m| self.result < m)
+    }
+
+    /// Concatenates all the values in `self` from left to right.
+    ///
+    /// If `self` has no values or the concatenation overflows, `None` is returned.
+    fn join_values(&self) -> Option<u64> {
+        let (first, rest) = self.values.split_first()?;
+        rest.iter().try_fold(*first, |acc, v| join(acc, *v, 10))
+    }
 }
 
+#[cfg(test)]
+impl Equation {
+    /// Generates a valid equation of `num_values` random values in `1..=999`, joined by random
+    /// operators picked from `ops` and evaluated left to right.
+    ///
+    /// Equations whose evaluation overflows are discarded and generated anew.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_values` is zero, or if it is greater than one and `ops` is empty.
+    fn random<R: rand::Rng>(rng: &mut R, num_values: usize, ops: &[Op]) -> Equation {
+        assert!(num_values > 0, "equations should have values");
+        loop {
+            let values: Vec<u64> = (0..num_values).map(|_| rng.random_range(1..=999)).collect();
+            let (first, rest) = values.split_first().expect("values should not be empty");
+
+            let result = rest.iter().try_fold(*first, |acc, v| {
+                ops[rng.random_range(0..ops.len())].apply(acc, *v)
+            });
+            if let Some(result) = result {
+                return Equation { result, values };
+            }
+        }
+    }
+
+    /// Generates an invalid equation of `num_values` random values in `1..=999`.
+    ///
+    /// The result of a valid equation is offset by a random non-zero delta, retrying until no
+    /// combination of operators can produce it.
+    fn random_invalid<R: rand::Rng>(rng: &mut R, num_values: usize) -> Equation {
+        loop {
+            let mut eq = Equation::random(rng, num_values, &[Op::Add, Op::Mul, Op::Concat]);
+            let Some(result) = eq.result.checked_add(rng.random_range(1..=999)) else {
+                continue;
+            };
+            eq.result = result;
+            if !eq.is_valid() {
+                return eq;
+            }
+        }
+    }
+}
+
+/// Reasons why a number cannot be disjoined from another.
+#[derive(Debug, PartialEq)]
+enum DisjoinError {
+    /// The suffix is zero.
+    ZeroSuffix,
+    /// The suffix is greater than the value it should be disjoined from.
+    SuffixExceedsValue,
+    /// The value does not end with the suffix.
+    NotASuffix,
+}
+
+impl fmt::Display for DisjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisjoinError::ZeroSuffix => write!(f, "suffix is zero"),
+            DisjoinError::SuffixExceedsValue => write!(f, "suffix exceeds value"),
+            DisjoinError::NotASuffix => write!(f, "not a suffix"),
+        }
+    }
+}
+
+impl error::Error for DisjoinError {}
+
 /// Disjoins `y` from `x` and returns the result.
 ///
 /// If `y` is zero, less than `x` or not concatenated to `x`, `None` is returned.
@@ -112,13 +609,101 @@ impl Equation {
 /// assert_eq!(disjoin(1234, 34), Some(12));
 /// ```
 fn disjoin(x: u64, y: u64) -> Option<u64> {
-    let digits = y.checked_ilog10()? + 1;
+    disjoin_checked(x, y).ok()
+}
+
+/// Disjoins `y` from `x` and returns the result.
+///
+/// # Errors
+///
+/// Returns an error describing why `y` could not be disjoined from `x`.
+fn disjoin_checked(x: u64, y: u64) -> Result<u64, DisjoinError> {
+    let digits = y.checked_ilog10().ok_or(DisjoinError::ZeroSuffix)? + 1;
+    if y > x {
+        return Err(DisjoinError::SuffixExceedsValue);
+    }
     let divisor = 10u64.pow(digits);
-    if y > x || x % divisor != y {
-        return None;
+    if x % divisor != y {
+        return Err(DisjoinError::NotASuffix);
     }
 
-    Some(x / divisor)
+    Ok(x / divisor)
+}
+
+/// Subtracts the addend `y` from `x` and returns the result.
+///
+/// # Errors
+///
+/// Returns an error if `y` exceeds `x`.
+fn subtract(x: u64, y: u64) -> Result<u64, Error> {
+    x.checked_sub(y).ok_or(Error::UnderflowInValidation)
+}
+
+/// Concatenates the digits of `y`, written in `base`, to `x` and returns the result.
+///
+/// If the result overflows, `None` is returned.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(join(12, 34, 10), Some(1234));
+/// ```
+fn join(x: u64, y: u64, base: u64) -> Option<u64> {
+    let digits = y.checked_ilog(base).unwrap_or(0) + 1;
+    x.checked_mul(base.checked_pow(digits)?)?.checked_add(y)
+}
+
+/// Concatenates the decimal digits of `y` to `x` and returns the result, undoing `disjoin`.
+///
+/// Unlike `join`, a zero `y` is rejected like `disjoin` does, so that `disjoin(z, y) == Some(x)`
+/// whenever `join_decimal(x, y) == Some(z)`. If `y` is zero or the result overflows, `None` is
+/// returned.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(join_decimal(12, 34), Some(1234));
+/// ```
+fn join_decimal(x: u64, y: u64) -> Option<u64> {
+    let multiplier = 10u64.checked_pow(y.checked_ilog10()? + 1)?;
+    x.checked_mul(multiplier)?.checked_add(y)
+}
+
+/// Checks if placing `ops` between `values` produces `result`.
+///
+/// Operators are evaluated strictly left to right. An overflowing evaluation never produces
+/// `result`, and neither do empty `values`.
+///
+/// # Errors
+///
+/// Returns an error if there is not exactly one operator less than there are values.
+fn verify_strict(result: u64, values: &[u64], ops: &[Op]) -> Result<bool, VerifyError> {
+    let expected = values.len().saturating_sub(1);
+    if ops.len() != expected {
+        return Err(VerifyError::WrongLength {
+            expected,
+            got: ops.len(),
+        });
+    }
+
+    let Some((first, rest)) = values.split_first() else {
+        return Ok(false);
+    };
+    let total = rest
+        .iter()
+        .zip(ops)
+        .try_fold(*first, |acc, (v, op)| op.apply(acc, *v));
+    Ok(total == Some(result))
+}
+
+/// Removes the equations with the same result and values as an earlier one, preserving the order
+/// of the rest.
+fn deduplicate(equations: Vec<Equation>) -> Vec<Equation> {
+    equations
+        .into_iter()
+        .collect::<IndexSet<_>>()
+        .into_iter()
+        .collect()
 }
 
 /// Sums the results of `equations`.
@@ -129,14 +714,32 @@ where
     equations.into_iter().map(|e| e.result).sum()
 }
 
+/// Counts how many times each value appears across `equations`.
+fn values_histogram(equations: &[Equation]) -> HashMap<u64, usize> {
+    aoc2024::count_occurrences(equations.iter().flat_map(Equation::values_iter))
+        .into_iter()
+        .collect()
+}
+
+/// Counts how many times each result appears across `equations`.
+fn result_histogram(equations: &[Equation]) -> HashMap<u64, usize> {
+    aoc2024::count_occurrences(equations.iter().map(|e| e.result))
+        .into_iter()
+        .collect()
+}
+
+/// Returns the value appearing most often across `equations`, along with its count.
+///
+/// Ties are broken in favour of the lowest value. If there are no values, `None` is returned.
+fn most_common_value(equations: &[Equation]) -> Option<(u64, usize)> {
+    aoc2024::count_occurrences(equations.iter().flat_map(Equation::values_iter))
+        .into_iter()
+        .max_by_key(|&(v, count)| (count, Reverse(v)))
+}
+
 fn main() -> Result<(), Box<dyn error::Error>> {
     let dataset = aoc2024::get_dataset(&PathBuf::from(file!()), "input.txt");
-    let reader = BufReader::new(File::open(dataset)?);
-
-    let mut eqs = vec![];
-    for line in reader.lines() {
-        eqs.push(Equation::from_str(&line?)?);
-    }
+    let eqs = Equation::batch_from_file_strict(&dataset)?;
 
     let valid_eqs: Vec<_> = eqs.into_iter().filter(Equation::is_valid).collect();
     let total = sum_results(valid_eqs);
@@ -148,6 +751,9 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::hash::{BuildHasher, RandomState};
 
     fn get_test_equations() -> Vec<Equation> {
         let lines = &[
@@ -168,6 +774,85 @@ mod tests {
             .unwrap()
     }
 
+    #[test]
+    fn equation_values_are_counted() {
+        let eqs = get_test_equations();
+
+        let values = values_histogram(&eqs);
+        assert_eq!(values[&13], 2);
+        assert_eq!(values[&6], 4);
+        assert_eq!(values.values().sum::<usize>(), 27);
+
+        let results = result_histogram(&eqs);
+        assert_eq!(results.len(), 9);
+        assert!(results.values().all(|&count| count == 1));
+
+        assert_eq!(most_common_value(&eqs), Some((6, 4)));
+        assert_eq!(most_common_value(&eqs[..1]), Some((10, 1)));
+        assert_eq!(most_common_value(&[]), None);
+    }
+
+    #[test]
+    fn equation_batches_collect_errors() {
+        let data = "190: 10 19\n83: 17 x\n3267: 81 40 27\n\n156: 15 6";
+        let (eqs, errors) = Equation::from_reader_lenient(io::Cursor::new(data));
+
+        assert_eq!(
+            eqs.iter().map(|e| e.result).collect::<Vec<_>>(),
+            [190, 3267, 156]
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, 2);
+        assert!(matches!(errors[0].1, Error::MalformedOperand(_)));
+        assert_eq!(errors[1].0, 4);
+
+        let err = Equation::from_reader(io::Cursor::new(data)).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(matches!(err.error, Error::MalformedOperand(_)));
+
+        let eqs = Equation::from_reader(io::Cursor::new("190: 10 19\n83: 17 5"));
+        assert_eq!(eqs.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn equations_are_read_from_reader() {
+        let data = "190: 10 19\n3267: 81 40 27\n83: 17 5";
+        let eqs = Equation::from_reader(io::Cursor::new(data)).unwrap();
+        let manual: Vec<_> = data
+            .lines()
+            .map(|l| Equation::from_str(l).unwrap())
+            .collect();
+        assert_eq!(eqs, manual);
+
+        let err = Equation::from_reader(io::Cursor::new("190: 10 19\n83: 17 x")).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(matches!(err.error, Error::MalformedOperand(_)));
+    }
+
+    #[test]
+    fn reader_variants_agree() {
+        let data = get_test_equations()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(data.lines().count(), 9);
+
+        let strict = Equation::from_reader(io::Cursor::new(&data)).unwrap();
+        let (lenient, errors) = Equation::from_reader_lenient(io::Cursor::new(&data));
+        let parallel = Equation::from_reader_parallel(io::Cursor::new(&data)).unwrap();
+        assert_eq!(strict, get_test_equations());
+        assert_eq!(lenient, strict);
+        assert!(errors.is_empty());
+        assert_eq!(parallel, strict);
+
+        let data = "190: 10 19\n83: 17 x\n3267: 81 40 27\n\n156: 15 6";
+        let err = Equation::from_reader(io::Cursor::new(data)).unwrap_err();
+        let par_err = Equation::from_reader_parallel(io::Cursor::new(data)).unwrap_err();
+        assert_eq!(par_err.line, err.line);
+        assert!(matches!(par_err.error, Error::MalformedOperand(_)));
+    }
+
     #[test]
     fn equations_are_validated_successfully() {
         let es = get_test_equations();
@@ -205,6 +890,243 @@ mod tests {
         assert!(!e.is_valid());
     }
 
+    #[test]
+    fn addition_is_found_as_only_operator() {
+        for e in ["10: 3 7", "20: 5 5 10", "12: 1 2 9"] {
+            let e = Equation::from_str(e).unwrap();
+            assert!(e.is_valid(), "{e}");
+            assert!(e.is_valid_recursive(), "{e}");
+        }
+    }
+
+    #[test]
+    fn addends_exceeding_total_do_not_underflow() {
+        for e in ["5: 1 10", "15: 5 4 20", "3: 2 2 2"] {
+            let e = Equation::from_str(e).unwrap();
+            assert!(!e.is_valid(), "{e}");
+            assert!(!e.is_valid_recursive(), "{e}");
+        }
+        assert_eq!(subtract(10, 4).unwrap(), 6);
+        assert!(matches!(subtract(4, 10), Err(Error::UnderflowInValidation)));
+    }
+
+    #[test]
+    fn equations_without_values_are_rejected() {
+        assert!(matches!(
+            "190:".parse::<Equation>(),
+            Err(Error::EmptyValues)
+        ));
+        assert!(matches!("1:".parse::<Equation>(), Err(Error::EmptyValues)));
+        assert_eq!(get_test_equations().len(), 9);
+
+        let e = Equation::from_str("19: 19").unwrap();
+        assert!(e.is_valid());
+        let e = Equation::from_str("12: 2 6").unwrap();
+        assert!(e.is_valid());
+        let e = Equation::from_str("13: 2 6").unwrap();
+        assert!(!e.is_valid());
+    }
+
+    #[test]
+    fn equations_are_sorted() {
+        let mut es = get_test_equations();
+        es.sort();
+        let results: Vec<_> = es.iter().map(|e| e.result).collect();
+        assert_eq!(
+            results,
+            [83, 156, 190, 192, 292, 3267, 7290, 21037, 161_011]
+        );
+
+        let a = Equation::from_str("10: 5 5").unwrap();
+        let b = Equation::from_str("10: 2 5").unwrap();
+        assert!(b < a);
+        assert_eq!(a, Equation::from_str("10: 5 5").unwrap());
+    }
+
+    #[test]
+    fn equations_are_displayed_as_puzzle_input() {
+        for e in get_test_equations() {
+            assert_eq!(e.to_string().parse::<Equation>().unwrap(), e);
+        }
+
+        let e = Equation::from_str("7: 7").unwrap();
+        assert_eq!(e.to_string(), "7: 7");
+        assert_eq!(format!("{e:?}"), "7 <- [7]");
+
+        let e = Equation::from_str("190: 10 19").unwrap();
+        assert_eq!(e.to_string(), "190: 10 19");
+        assert_eq!(format!("{e:?}"), "190 <- [10, 19]");
+
+        let line = "55: 1 2 3 4 5 6 7 8 9 10";
+        let e = Equation::from_str(line).unwrap();
+        assert_eq!(e.value_count(), 10);
+        assert_eq!(e.to_string(), line);
+        assert_eq!(e.to_string().parse::<Equation>().unwrap(), e);
+    }
+
+    #[test]
+    fn equations_are_deduplicated() {
+        let a = Equation::from_str("190: 10 19").unwrap();
+        let b = Equation::from_str("190: 10 19").unwrap();
+        let hasher = RandomState::new();
+        assert_eq!(a, b);
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&b));
+        assert_ne!(a, Equation::from_str("190: 19 10").unwrap());
+
+        let mut es = get_test_equations();
+        es.insert(3, Equation::from_str("190: 10 19").unwrap());
+        es.push(Equation::from_str("3267: 81 40 27").unwrap());
+        assert_eq!(es.iter().collect::<HashSet<_>>().len(), 9);
+        assert_eq!(deduplicate(es), get_test_equations());
+    }
+
+    #[test]
+    fn equations_are_sorted_by_difficulty() {
+        let es = get_test_equations();
+        let solutions: Vec<_> = es.iter().map(|e| e.all_valid_sequences().len()).collect();
+        assert_eq!(solutions, [1, 2, 0, 1, 1, 0, 1, 0, 1]);
+        assert_eq!(
+            es[1].all_valid_sequences(),
+            [[Op::Add, Op::Mul], [Op::Mul, Op::Add]]
+        );
+        for (e, &n) in es.iter().zip(&solutions) {
+            assert_eq!(e.is_valid(), n > 0);
+        }
+
+        let mut sorted = get_test_equations();
+        sorted.sort_by_key(Equation::by_difficulty);
+        let mut expected = get_test_equations();
+        expected.sort_by_key(|e| e.all_valid_sequences().len());
+        assert_eq!(sorted, expected);
+        assert_eq!(sorted.last(), Some(&es[1]));
+    }
+
+    #[test]
+    fn equations_are_split_and_merged() {
+        let es = get_test_equations();
+        assert_eq!(es[0].split_at(1).unwrap(), (&[10][..], &[19][..]));
+        assert_eq!(es[1].split_at(2).unwrap(), (&[81, 40][..], &[27][..]));
+        for index in [0, 3] {
+            assert!(matches!(
+                es[1].split_at(index),
+                Err(Error::SplitOutOfRange { len: 3, .. })
+            ));
+        }
+
+        // 3267: 81 40 27 is solved by 81 + 40 * 27.
+        let (left, right) = es[1].split_at(2).unwrap();
+        let left = Equation {
+            result: 121,
+            values: left.to_vec(),
+        };
+        let right = Equation {
+            result: 27,
+            values: right.to_vec(),
+        };
+        assert!(left.is_valid() && right.is_valid());
+        let merged = Equation::merge(&left, Op::Mul, &right).unwrap();
+        assert_eq!(merged, es[1]);
+        assert!(merged.is_valid());
+
+        let a = Equation::from_str("15: 15").unwrap();
+        let b = Equation::from_str("6: 6").unwrap();
+        assert_eq!(Equation::merge(&a, Op::Concat, &b).unwrap(), es[3]);
+
+        let max = Equation::from_str(&format!("{}: 1", u64::MAX)).unwrap();
+        assert!(matches!(
+            Equation::merge(&max, Op::Add, &b),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn equation_structure_is_reported() {
+        let es = get_test_equations();
+        let counts: Vec<_> = es.iter().map(Equation::value_count).collect();
+        assert_eq!(counts, [2, 3, 2, 2, 4, 3, 3, 4, 4]);
+        for e in &es {
+            assert_eq!(e.operator_count(), e.value_count() - 1);
+            assert!(!e.is_trivial());
+            if e.is_valid() {
+                assert!(e.min_result_possible() <= e.result);
+            }
+        }
+        assert_eq!(es[0].min_result_possible(), 29);
+
+        let e = Equation::from_str("7: 7").unwrap();
+        assert!(e.is_trivial());
+        assert_eq!(e.operator_count(), 0);
+        assert_eq!(e.min_result_possible(), 7);
+
+        // Multiplying by 1 produces less than adding it.
+        let e = Equation::from_str("5: 5 1").unwrap();
+        assert!(e.is_valid());
+        assert_eq!(e.min_result_possible(), 5);
+    }
+
+    #[test]
+    fn operator_sequences_are_verified() {
+        let es = get_test_equations();
+        let solutions: &[(usize, &[Op])] = &[
+            (0, &[Op::Mul]),
+            (1, &[Op::Add, Op::Mul]),
+            (1, &[Op::Mul, Op::Add]),
+            (3, &[Op::Concat]),
+            (4, &[Op::Mul, Op::Concat, Op::Mul]),
+            (6, &[Op::Concat, Op::Add]),
+            (8, &[Op::Add, Op::Mul, Op::Add]),
+        ];
+        for (i, ops) in solutions {
+            assert_eq!(es[*i].verify(ops), Ok(true));
+        }
+
+        assert_eq!(es[0].verify(&[Op::Add]), Ok(false));
+        assert_eq!(es[2].verify(&[Op::Concat]), Ok(false));
+        assert_eq!(
+            es[1].verify(&[Op::Add]),
+            Err(VerifyError::WrongLength {
+                expected: 2,
+                got: 1
+            })
+        );
+        assert_eq!(verify_strict(190, &[10, 19], &[Op::Mul]), Ok(true));
+        assert_eq!(verify_strict(0, &[u64::MAX, 2], &[Op::Mul]), Ok(false));
+    }
+
+    #[test]
+    fn scaled_equations_are_validated() {
+        let e = Equation::from_str("30: 10 20").unwrap();
+        let scaled = e.scale(3).unwrap();
+        assert_eq!((scaled.result, scaled.values), (90, vec![30, 60]));
+        assert!(e.is_valid());
+        assert!(e.is_valid_scaled(3));
+
+        let e = Equation::from_str("156: 15 6").unwrap();
+        assert!(e.is_valid());
+        assert!(!e.is_valid_scaled(10));
+
+        assert!(e.scale(u64::MAX).is_none());
+        assert!(!e.is_valid_scaled(u64::MAX));
+    }
+
+    #[test]
+    fn impossible_equations_are_rejected_early() {
+        let e = Equation::from_str("1: 2 3").unwrap();
+        assert!(e.quick_reject());
+        assert!(!e.is_valid());
+
+        for e in get_test_equations() {
+            assert!(!e.quick_reject());
+        }
+
+        let e = Equation {
+            result: 1,
+            values: vec![],
+        };
+        assert!(!e.quick_reject());
+        assert!(!e.is_valid());
+    }
+
     #[test]
     fn disjoining_numbers_succeeds_for_valid_numbers() {
         assert_eq!(disjoin(1234, 34), Some(12));
@@ -213,4 +1135,109 @@ mod tests {
         assert!(disjoin(34, 1234).is_none());
         assert!(disjoin(0, 0).is_none());
     }
+
+    #[test]
+    fn disjoining_numbers_reports_failure_reason() {
+        assert_eq!(disjoin_checked(1234, 34), Ok(12));
+        assert_eq!(disjoin_checked(0, 0), Err(DisjoinError::ZeroSuffix));
+        assert_eq!(
+            disjoin_checked(34, 1234),
+            Err(DisjoinError::SuffixExceedsValue)
+        );
+        assert_eq!(disjoin_checked(1234, 35), Err(DisjoinError::NotASuffix));
+    }
+
+    #[test]
+    fn joining_numbers_succeeds_for_valid_numbers() {
+        assert_eq!(join(12, 34, 10), Some(1234));
+        assert_eq!(join(12, 0, 10), Some(120));
+        assert_eq!(join(0b10, 0b11, 2), Some(0b1011));
+        assert!(join(u64::MAX, 1, 10).is_none());
+    }
+
+    #[test]
+    fn joining_undoes_disjoining() {
+        assert_eq!(join_decimal(12, 34), Some(1234));
+        assert_eq!(disjoin(1234, 34), Some(12));
+        assert_eq!(join_decimal(0, 5), Some(5));
+        assert!(join_decimal(5, 0).is_none());
+        assert!(join_decimal(u64::MAX, 1).is_none());
+        assert!(join_decimal(u64::MAX / 10, 99).is_none());
+
+        for x in (0..1000).chain([u64::MAX / 10_000]) {
+            for y in 1..1000 {
+                let z = join_decimal(x, y).unwrap();
+                assert_eq!(Some(z), join(x, y, 10));
+                assert_eq!(disjoin(z, y), Some(x), "{x} || {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn equation_values_are_joined_left_to_right() {
+        let e = Equation::from_str("190: 1 90").unwrap();
+        assert_eq!(e.join_values(), Some(190));
+        assert!(e.is_valid());
+
+        let e = Equation::from_str("12345: 1 23 45").unwrap();
+        assert_eq!(e.join_values(), Some(12345));
+        assert!(e.is_valid());
+
+        let e = Equation {
+            result: 0,
+            values: vec![u64::MAX, 1],
+        };
+        assert!(e.join_values().is_none());
+    }
+
+    #[test]
+    fn iterative_validation_matches_recursive() {
+        for e in get_test_equations() {
+            assert_eq!(e.is_valid_iterative(), e.is_valid_recursive(), "{e:?}");
+        }
+
+        let mut rng = StdRng::seed_from_u64(11);
+        for n in 0..200 {
+            let ops = [Op::Add, Op::Mul, Op::Concat];
+            let e = if n % 2 == 0 {
+                Equation::random(&mut rng, 1 + n % 6, &ops)
+            } else {
+                Equation::random_invalid(&mut rng, 1 + n % 6)
+            };
+            assert_eq!(e.is_valid_iterative(), e.is_valid_recursive(), "{e:?}");
+        }
+    }
+
+    #[test]
+    fn long_equations_are_validated() {
+        let e = Equation {
+            result: 50,
+            values: vec![1; 50],
+        };
+        assert!(e.is_valid());
+
+        let e = Equation {
+            result: 1,
+            values: vec![1; 50],
+        };
+        assert!(e.is_valid());
+    }
+
+    #[test]
+    fn random_equations_are_validated() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for n in 0..100 {
+            let eq = Equation::random(&mut rng, 1 + n % 5, &[Op::Add, Op::Mul, Op::Concat]);
+            assert!(eq.is_valid(), "{eq:?} should be valid");
+        }
+        for n in 0..100 {
+            let eq = Equation::random(&mut rng, 1 + n % 5, &[Op::Add]);
+            assert_eq!(eq.result, eq.values.iter().sum());
+        }
+        for n in 0..100 {
+            let eq = Equation::random_invalid(&mut rng, 1 + n % 5);
+            assert!(!eq.is_valid(), "{eq:?} should be invalid");
+        }
+    }
 }
@@ -1,6 +1,52 @@
 #![warn(clippy::pedantic)]
 
+use std::collections::{hash_map, HashMap};
+use std::hash::Hash;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A stopwatch measuring the execution time of a solution.
+#[derive(Clone, Copy, Debug)]
+pub struct Timer {
+    /// When the current measurement started.
+    start: Instant,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timer {
+    /// Returns a new `Timer`, started right away.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the time elapsed since `self` was started.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Returns the time elapsed since `self` was started, in milliseconds.
+    #[must_use]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed().as_secs_f64() * 1000.0
+    }
+
+    /// Returns the time elapsed since `self` was started, then restarts it.
+    pub fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.start;
+        self.start = now;
+        elapsed
+    }
+}
 
 /// Builds the path to a dataset paired to a specific solution binary.
 ///
@@ -27,3 +73,190 @@ pub fn get_dataset(source_path: &Path, dataset_name: &str) -> PathBuf {
     .iter()
     .collect()
 }
+
+/// The amount of occurrences of each distinct item in a collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrequencyTable<T: Eq + Hash>(HashMap<T, usize>);
+
+impl<T: Eq + Hash> FrequencyTable<T> {
+    /// Returns the amount of occurrences of `key`.
+    #[must_use]
+    pub fn frequency(&self, key: &T) -> usize {
+        self.0.get(key).copied().unwrap_or(0)
+    }
+
+    /// Returns the rank of `key` by frequency, starting from 1 for the most frequent items.
+    ///
+    /// Items with the same frequency share a rank, and the following rank is skipped as many
+    /// times. Items that never occur have rank 0.
+    #[must_use]
+    pub fn rank(&self, key: &T) -> usize {
+        let frequency = self.frequency(key);
+        if frequency == 0 {
+            return 0;
+        }
+        1 + self.0.values().filter(|&&f| f > frequency).count()
+    }
+
+    /// Returns the `n` most frequent items, along with their frequency.
+    ///
+    /// Items are sorted by descending frequency. The order of items with the same frequency is
+    /// unspecified.
+    #[must_use]
+    pub fn top_n(&self, n: usize) -> Vec<(&T, usize)> {
+        let mut items: Vec<_> = self.0.iter().map(|(k, &f)| (k, f)).collect();
+        items.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+        items.truncate(n);
+        items
+    }
+
+    /// Returns the total amount of occurrences of all items.
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+}
+
+impl<T: Eq + Hash> FromIterator<T> for FrequencyTable<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut m = HashMap::new();
+        for item in iter {
+            *m.entry(item).or_default() += 1;
+        }
+        Self(m)
+    }
+}
+
+impl<T: Clone + Eq + Hash> From<&[T]> for FrequencyTable<T> {
+    fn from(items: &[T]) -> Self {
+        items.iter().cloned().collect()
+    }
+}
+
+impl<T: Eq + Hash> IntoIterator for FrequencyTable<T> {
+    type Item = (T, usize);
+    type IntoIter = hash_map::IntoIter<T, usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Counts the occurrences of each distinct item in `items`.
+#[must_use]
+pub fn count_occurrences<T, I>(items: I) -> FrequencyTable<T>
+where
+    T: Eq + Hash,
+    I: IntoIterator<Item = T>,
+{
+    items.into_iter().collect()
+}
+
+/// Runs `f` and returns its result, along with the time it took to run.
+pub fn time<F, T>(f: F) -> (T, Duration)
+where
+    F: FnOnce() -> T,
+{
+    let timer = Timer::new();
+    let result = f();
+    (result, timer.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Eq, Hash, PartialEq)]
+    enum Shape {
+        Circle,
+        Square,
+        Triangle,
+    }
+
+    #[test]
+    fn occurrences_are_counted_for_integers() {
+        let m = count_occurrences([4, 3, 5, 3, 9, 3]);
+
+        assert_eq!(
+            HashMap::from_iter(m),
+            HashMap::from([(4, 1), (3, 3), (5, 1), (9, 1)])
+        );
+    }
+
+    #[test]
+    fn occurrences_are_counted_for_chars() {
+        let m = count_occurrences("XMASMAS".chars());
+
+        assert_eq!(
+            HashMap::from_iter(m),
+            HashMap::from([('X', 1), ('M', 2), ('A', 2), ('S', 2)])
+        );
+    }
+
+    #[test]
+    fn occurrences_are_counted_for_custom_types() {
+        let m = count_occurrences([Shape::Square, Shape::Circle, Shape::Square]);
+
+        assert_eq!(m.frequency(&Shape::Square), 2);
+        assert_eq!(m.frequency(&Shape::Circle), 1);
+        assert_eq!(m.frequency(&Shape::Triangle), 0);
+        assert_eq!(
+            HashMap::from_iter(m),
+            HashMap::from([(Shape::Square, 2), (Shape::Circle, 1)])
+        );
+    }
+
+    #[test]
+    fn frequency_table_ranks_items() {
+        let items: &[char] = &['a', 'b', 'b', 'c', 'c', 'c', 'd', 'd', 'd', 'd', 'e'];
+        let m = FrequencyTable::from(items);
+
+        assert_eq!(m.frequency(&'c'), 3);
+        assert_eq!(m.frequency(&'z'), 0);
+        assert_eq!(m.total(), items.len());
+
+        assert_eq!(m.rank(&'d'), 1);
+        assert_eq!(m.rank(&'c'), 2);
+        assert_eq!(m.rank(&'b'), 3);
+        assert_eq!(m.rank(&'a'), 4);
+        assert_eq!(m.rank(&'e'), 4);
+        assert_eq!(m.rank(&'z'), 0);
+
+        assert_eq!(m.top_n(3), [(&'d', 4), (&'c', 3), (&'b', 2)]);
+        assert_eq!(m.top_n(10).len(), 5);
+        assert!(m.top_n(0).is_empty());
+
+        assert_eq!(m.into_iter().map(|(_, f)| f).sum::<usize>(), items.len());
+    }
+
+    #[test]
+    fn timer_measures_elapsed_time() {
+        let timer = Timer::new();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(timer.elapsed() >= Duration::from_millis(5));
+        assert!(timer.elapsed_ms() >= 5.0);
+    }
+
+    #[test]
+    fn timer_lap_restarts_measurement() {
+        let mut timer = Timer::new();
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Once restarted, the timer cannot have run for longer than a clock started before the
+        // lap, no matter how long reading both takes.
+        let before_lap = Instant::now();
+        let lap = timer.lap();
+        let elapsed = timer.elapsed();
+        assert!(lap >= Duration::from_millis(20));
+        assert!(elapsed <= before_lap.elapsed());
+    }
+
+    #[test]
+    fn timed_function_returns_its_result() {
+        let (result, elapsed) = time(|| (1..=10).sum::<u32>());
+
+        assert_eq!(result, 55);
+        assert!(elapsed < Duration::from_secs(1));
+    }
+}